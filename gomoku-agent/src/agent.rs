@@ -1,11 +1,58 @@
 use figment::Figment;
-use gomoku_core::game::Game;
-use std::error::Error;
+use gomoku_core::game::{Game, PlaceStoneError};
+use std::io;
+use tch::TchError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid config: {0}")]
+    Config(Box<figment::Error>),
+    #[error("tensor error: {0}")]
+    Tensor(TchError),
+    #[error("illegal move: {0}")]
+    IllegalMove(#[from] PlaceStoneError),
+    #[error("no legal moves, board full")]
+    NoLegalMoves,
+    #[error("this agent does not support {operation}")]
+    Unsupported { operation: &'static str },
+}
+
+impl From<figment::Error> for AgentError {
+    fn from(err: figment::Error) -> Self {
+        Self::Config(Box::new(err))
+    }
+}
+
+impl From<TchError> for AgentError {
+    fn from(err: TchError) -> Self {
+        match err {
+            TchError::Io(err) => Self::Io(err),
+            err => Self::Tensor(err),
+        }
+    }
+}
 
 pub trait Agent {
-    fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
-    fn load(&mut self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
-    fn train(&mut self, epoch: usize, options: Figment)
-        -> Result<(), Box<dyn Error + Send + Sync>>;
-    fn next_move(&mut self, game: &Game) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn save(&self, path: &str) -> Result<(), AgentError>;
+    fn load(&mut self, path: &str) -> Result<(), AgentError>;
+    /// Serializes the agent in memory, e.g. to send it over a network.
+    fn to_bytes(&self) -> Result<Vec<u8>, AgentError> {
+        Err(AgentError::Unsupported {
+            operation: "serializing to bytes",
+        })
+    }
+    /// Restores the agent from bytes produced by `to_bytes`.
+    #[allow(clippy::wrong_self_convention)]
+    fn from_bytes(&mut self, _bytes: &[u8]) -> Result<(), AgentError> {
+        Err(AgentError::Unsupported {
+            operation: "deserializing from bytes",
+        })
+    }
+    fn train(&mut self, epoch: usize, options: Figment) -> Result<(), AgentError>;
+    fn next_move(&mut self, game: &Game) -> Result<usize, AgentError>;
+    /// Clears any state carried between moves or games, such as cached evaluations.
+    fn reset(&mut self) {}
 }
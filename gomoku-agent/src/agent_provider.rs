@@ -1,6 +1,33 @@
-use crate::agent::Agent;
+use crate::{
+    agent::Agent,
+    agents::{first_move::FirstMoveProvider, gomoku_ddqn::GomokuDDQNProvider},
+};
 
 pub trait AgentProvider {
     fn name(&self) -> String;
     fn create_agent(&self) -> Box<dyn Agent>;
 }
+
+/// Lists the providers of every available agent.
+pub fn providers() -> Vec<Box<dyn AgentProvider>> {
+    vec![Box::new(GomokuDDQNProvider), Box::new(FirstMoveProvider)]
+}
+
+/// Finds the provider with the given name.
+pub fn find_provider(name: &str) -> Option<Box<dyn AgentProvider>> {
+    providers()
+        .into_iter()
+        .find(|provider| provider.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_provider() {
+        assert_eq!(find_provider("first-move").unwrap().name(), "first-move");
+        assert_eq!(find_provider("gomoku-ddqn").unwrap().name(), "gomoku-ddqn");
+        assert!(find_provider("unknown").is_none());
+    }
+}
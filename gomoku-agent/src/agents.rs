@@ -1 +1,3 @@
+pub mod first_move;
 pub mod gomoku_ddqn;
+pub mod scripted;
@@ -0,0 +1,72 @@
+use crate::{
+    agent::{Agent, AgentError},
+    agent_provider::AgentProvider,
+};
+use figment::Figment;
+use gomoku_core::game::Game;
+
+/// A baseline agent that always plays the lowest-index legal move.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FirstMoveAgent;
+
+impl Agent for FirstMoveAgent {
+    fn save(&self, _path: &str) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+        game.board()
+            .legal_moves()
+            .first()
+            .copied()
+            .ok_or(AgentError::NoLegalMoves)
+    }
+}
+
+pub struct FirstMoveProvider;
+
+impl AgentProvider for FirstMoveProvider {
+    fn name(&self) -> String {
+        "first-move".to_owned()
+    }
+
+    fn create_agent(&self) -> Box<dyn Agent> {
+        Box::new(FirstMoveAgent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_first_move() {
+        let mut rng = rand::thread_rng();
+        let mut agent = FirstMoveProvider.create_agent();
+
+        for _ in 0..10 {
+            let mut game = Game::new(15, 5);
+
+            while game.game_result().is_none() {
+                let legal_moves = game.board().legal_moves();
+                let index = agent.next_move(&game).unwrap();
+
+                assert!(legal_moves.contains(&index));
+                assert_eq!(Some(&index), legal_moves.iter().min());
+
+                // let the game move on from random positions rather than the agent's own
+                game.place_stone(*legal_moves.choose(&mut rng).unwrap())
+                    .unwrap();
+            }
+        }
+    }
+}
@@ -1,6 +1,22 @@
 mod agent;
+mod cache;
+mod logger;
 mod model;
+mod opening;
 mod provider;
+mod tie_break;
 mod trainer;
 
+pub use crate::nn_utils::NormKind;
+pub use agent::{GomokuDDQNAgent, MigrationReport};
+pub use logger::{
+    EpochReport, PlotLogger, RecentGameReport, StdoutLogger, StepReport, TensorBoardLogger,
+    TrainLogger,
+};
+pub use model::{
+    encode_batched_board_augmented, EncodingMode, ModelConfig, ModelConfigError, ResidualChannels,
+    StageTransition, Stages,
+};
+pub use opening::{OpeningEntry, OpeningReport};
 pub use provider::GomokuDDQNProvider;
+pub use tie_break::TieBreak;
@@ -1,21 +1,60 @@
 use super::{
+    cache::{position_key, OutputCache},
+    logger::{StdoutLogger, TrainLogger},
     model::{encode_batched_board, Model, ModelConfig},
-    trainer::GomokuDDQNTrainer,
+    opening::{opening_positions, OpeningEntry, OpeningReport},
+    tie_break::{select_move, TieBreak},
+    trainer::{eval, GomokuDDQNTrainer},
+};
+use crate::{
+    agent::{Agent, AgentError},
+    replay::{generate_history_boards, Opponent},
+};
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use gomoku_core::{
+    board::Board,
+    game::{Game, GameResult, Turn},
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    fs,
+    io::{self, Cursor},
+    path::Path,
 };
-use crate::{agent::Agent, replay::generate_history_boards};
-use figment::Figment;
-use gomoku_core::game::Game;
-use std::error::Error;
 use tch::{
     nn::{ModuleT, VarStore},
     utils::{has_cuda, has_mps, has_vulkan},
-    Device, Tensor,
+    Device, TchError, Tensor,
 };
 
+/// A double DQN agent.
+///
+/// `new` runs the agent on the first available device among CUDA, MPS, Vulkan and the CPU. Saved weights
+/// don't depend on that device: they are always loaded through the CPU and then moved to the
+/// agent's device, so a model saved on a GPU machine can be loaded on a CPU-only one and vice versa.
+///
+/// With separate color networks enabled, `model` plays black and a second network with its own
+/// variables plays white. The white network is saved next to the black one, with a `.white` suffix,
+/// and `load` enables separate color networks whenever it finds one.
+///
+/// `save` also writes the model config next to the weights, with a `.config.toml` suffix, so that
+/// `load_migrating` can tell which layers changed since.
+///
+/// `next_move` can cache the network outputs of recent positions; see `set_cache_capacity`.
 #[derive(Debug)]
 pub struct GomokuDDQNAgent {
     var_store: VarStore,
     model: Model,
+    white: Option<(VarStore, Model)>,
+    cache: OutputCache,
+    tie_break: TieBreak,
+    tie_break_rng: StdRng,
+    loggers: Vec<Box<dyn TrainLogger>>,
+    candidate_distance: Option<usize>,
+    last_policy_entropy: Option<f32>,
 }
 
 impl GomokuDDQNAgent {
@@ -29,10 +68,99 @@ impl GomokuDDQNAgent {
         } else {
             Device::Cpu
         };
+
+        Self::with_device(model_config, device)
+    }
+
+    pub fn with_device(model_config: ModelConfig, device: Device) -> Self {
         let var_store = VarStore::new(device);
         let model = Model::new(var_store.root().sub("gomoku-ddqn-agent"), model_config);
 
-        Self { var_store, model }
+        Self {
+            var_store,
+            model,
+            white: None,
+            cache: OutputCache::default(),
+            tie_break: TieBreak::default(),
+            tie_break_rng: StdRng::seed_from_u64(0),
+            loggers: vec![Box::new(StdoutLogger)],
+            candidate_distance: None,
+            last_policy_entropy: None,
+        }
+    }
+
+    /// Gives white its own network, initialized from the current one. Does nothing if white
+    /// already has one.
+    pub fn enable_separate_color_nets(&mut self) {
+        if self.white.is_some() {
+            return;
+        }
+
+        let var_store = VarStore::new(self.var_store.device());
+        let mut model = Model::new(
+            var_store.root().sub("gomoku-ddqn-agent"),
+            self.model.config().clone(),
+        );
+        model.copy_weights_from(&self.model, None);
+
+        self.white = Some((var_store, model));
+    }
+
+    pub fn has_separate_color_nets(&self) -> bool {
+        self.white.is_some()
+    }
+
+    /// Caches the network outputs of up to `capacity` positions in `next_move`, evicting the least
+    /// recently used ones. Zero disables the cache, which is the default.
+    ///
+    /// The cache is cleared on `reset`, and whenever the weights change through `load`,
+    /// `from_bytes` or training.
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.cache = OutputCache::new(capacity);
+    }
+
+    /// Sets how `next_move` picks among equally valued moves. `TieBreak::Random` reseeds the
+    /// generator with its seed.
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        if let TieBreak::Random(seed) = tie_break {
+            self.tie_break_rng = StdRng::seed_from_u64(seed);
+        }
+
+        self.tie_break = tie_break;
+    }
+
+    /// Sets the loggers receiving the training reports, replacing the default `StdoutLogger`.
+    pub fn set_loggers(&mut self, loggers: Vec<Box<dyn TrainLogger>>) {
+        self.loggers = loggers;
+    }
+
+    pub fn tie_break(&self) -> TieBreak {
+        self.tie_break
+    }
+
+    /// Restricts `next_move` to the legal moves within `distance` cells of an existing stone (see
+    /// `Board::candidate_moves`), or lifts the restriction with `None`, the default. Positions
+    /// without any such move, e.g. for a distance of zero, fall back to every legal move.
+    pub fn set_candidate_distance(&mut self, distance: Option<usize>) {
+        self.candidate_distance = distance;
+    }
+
+    pub fn candidate_distance(&self) -> Option<usize> {
+        self.candidate_distance
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// The number of `next_move` calls answered from the cache.
+    pub fn cache_hits(&self) -> usize {
+        self.cache.hits()
+    }
+
+    /// The number of `next_move` calls that had to evaluate the network with the cache enabled.
+    pub fn cache_misses(&self) -> usize {
+        self.cache.misses()
     }
 
     pub fn var_store(&self) -> &VarStore {
@@ -42,41 +170,862 @@ impl GomokuDDQNAgent {
     pub fn model(&self) -> &Model {
         &self.model
     }
+
+    /// The variables of the network playing `turn`.
+    pub fn var_store_for(&self, turn: Turn) -> &VarStore {
+        match (turn, &self.white) {
+            (Turn::White, Some((var_store, _))) => var_store,
+            _ => &self.var_store,
+        }
+    }
+
+    /// The network playing `turn`.
+    pub fn model_for(&self, turn: Turn) -> &Model {
+        match (turn, &self.white) {
+            (Turn::White, Some((_, model))) => model,
+            _ => &self.model,
+        }
+    }
+
+    fn white_path(path: &str) -> String {
+        format!("{}.white", path)
+    }
+
+    fn config_path(path: &str) -> String {
+        format!("{}.config.toml", path)
+    }
+
+    /// Loads weights saved with a possibly different model config.
+    ///
+    /// Every variable whose name and shape match the saved one is loaded, and the others keep their
+    /// fresh initialization. Whether the config saved next to the weights differs from the agent's
+    /// is reported in `MigrationReport::config_changed`. Not supported with separate color
+    /// networks.
+    pub fn load_migrating(&mut self, path: &str) -> Result<MigrationReport, AgentError> {
+        if self.white.is_some() {
+            return Err(AgentError::Unsupported {
+                operation: "load_migrating with separate color networks",
+            });
+        }
+
+        fs::metadata(path)?;
+        self.cache.clear();
+
+        let config_path = Self::config_path(path);
+        let saved_config = if Path::new(&config_path).exists() {
+            Some(ModelConfig::from_figment(
+                &Figment::new().merge(Toml::file(&config_path)),
+            )?)
+        } else {
+            None
+        };
+        let config_changed = saved_config
+            .as_ref()
+            .is_some_and(|config| config != self.model.config());
+
+        let saved = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("bin") | Some("pt") => Tensor::loadz_multi_with_device(path, Device::Cpu)?,
+            Some("safetensors") => Tensor::read_safetensors(path)?,
+            _ => Tensor::load_multi_with_device(path, Device::Cpu)?,
+        };
+
+        let mut report = MigrationReport {
+            saved_config,
+            config_changed,
+            migrated: vec![],
+            reinitialized: vec![],
+        };
+        let mut variables = self.var_store.variables().into_iter().collect::<Vec<_>>();
+        variables.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        for (name, mut variable) in variables {
+            match saved.iter().find(|(saved_name, _)| *saved_name == name) {
+                Some((_, tensor)) if tensor.size() == variable.size() => {
+                    tch::no_grad(|| variable.f_copy_(tensor))?;
+                    report.migrated.push(name);
+                }
+                _ => report.reinitialized.push(name),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Estimates the probability of each legal move leading to a win.
+    ///
+    /// This is a heuristic for display purposes only: the Q-values of the legal moves are squashed
+    /// into `[0, 1]` by a sigmoid, so the results are not calibrated probabilities.
+    pub fn move_win_probs(&self, game: &Game) -> Vec<(usize, f32)> {
+        let legal_moves = game.board().legal_moves();
+
+        if legal_moves.is_empty() {
+            return vec![];
+        }
+
+        let probs: Vec<f32> = self
+            .legal_move_values(game, &legal_moves)
+            .sigmoid()
+            .flatten(0, -1)
+            .try_into()
+            .unwrap();
+
+        legal_moves.into_iter().zip(probs).collect()
+    }
+
+    /// Returns up to `k` legal moves with the highest Q-values, best first, along with the values.
+    pub fn suggest_moves(&self, game: &Game, k: usize) -> Vec<(usize, f32)> {
+        let legal_moves = game.board().legal_moves();
+
+        if legal_moves.is_empty() {
+            return vec![];
+        }
+
+        let values: Vec<f32> = self
+            .legal_move_values(game, &legal_moves)
+            .flatten(0, -1)
+            .try_into()
+            .unwrap();
+
+        let mut moves = Vec::from_iter(legal_moves.into_iter().zip(values));
+        moves.sort_by(|(_, lhs), (_, rhs)| rhs.total_cmp(lhs));
+        moves.truncate(k);
+        moves
+    }
+
+    /// Lists the `k` moves the agent likes best in a few common openings: the empty board, a stone
+    /// in the center, and the center answered diagonally and directly. Meant for checking at a
+    /// glance that training produced sensible play; the report prints one line per position.
+    ///
+    /// The positions are played with the given win length; those a `win_len` out of range for the
+    /// model's board rules out are left out of the report.
+    pub fn opening_report(&self, win_len: usize, k: usize) -> OpeningReport {
+        let board_size = self.model.config().board_size;
+        let entries = opening_positions(board_size)
+            .into_iter()
+            .filter_map(|moves| {
+                let game = Game::from_moves(board_size, win_len, &moves).ok()?;
+                let top_moves = self.suggest_moves(&game, k);
+                Some(OpeningEntry { moves, top_moves })
+            })
+            .collect();
+
+        OpeningReport {
+            board_size,
+            entries,
+        }
+    }
+
+    /// Computes the entropy, in nats, of the softmax over the Q-values of the legal moves.
+    ///
+    /// It is `ln(n)` for `n` legal moves of equal value and approaches zero as the agent commits to
+    /// a single move, so a low value over many positions hints at a collapsed policy. Returns zero
+    /// when there is no legal move.
+    pub fn policy_entropy(&self, game: &Game) -> f32 {
+        let legal_moves = game.board().legal_moves();
+
+        if legal_moves.is_empty() {
+            return 0.0;
+        }
+
+        let values: Vec<f32> = self
+            .legal_move_values(game, &legal_moves)
+            .flatten(0, -1)
+            .try_into()
+            .unwrap();
+
+        softmax_entropy(&values)
+    }
+
+    /// Takes the policy entropy of the position of the last `next_move` call, computed from the
+    /// Q-values that call already evaluated, so unlike `policy_entropy` it needs no inference of its
+    /// own. With a candidate distance set, only the candidate moves count. Returns `None` if
+    /// `next_move` wasn't called since the last take.
+    pub fn take_policy_entropy(&mut self) -> Option<f32> {
+        self.last_policy_entropy.take()
+    }
+
+    /// Plays a whole game against itself, greedily on both sides, and returns the moves and the
+    /// result.
+    ///
+    /// Moves of equal value are chosen between with a random generator seeded by `seed`, so the
+    /// same seed always gives the same game.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `board_size` isn't the model's board size, or if `win_len` is out of range.
+    pub fn self_play(
+        &self,
+        board_size: usize,
+        win_len: usize,
+        seed: u64,
+    ) -> (Vec<usize>, GameResult) {
+        assert_eq!(
+            board_size,
+            self.model.config().board_size,
+            "the board size must match the model's"
+        );
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut game = Game::new(board_size, win_len);
+
+        loop {
+            if let Some(game_result) = game.game_result() {
+                return (game.moves().to_vec(), game_result);
+            }
+
+            let legal_moves = game.board().legal_moves();
+            let legal_values: Vec<f32> = self
+                .legal_move_values(&game, &legal_moves)
+                .flatten(0, -1)
+                .try_into()
+                .unwrap();
+
+            let mut values = vec![f32::NEG_INFINITY; board_size * board_size];
+            for (index, value) in legal_moves.into_iter().zip(legal_values) {
+                values[index] = value;
+            }
+
+            let index = select_move(&values, board_size, TieBreak::Random(seed), &mut rng).unwrap();
+            game.place_stone(index).unwrap();
+        }
+    }
+
+    /// Chooses a move like `next_move`, but with the position turned into its canonical
+    /// orientation first, so that every rotation/reflection of a position gets the same move, mapped
+    /// back to it.
+    ///
+    /// The orientation is the one `Board::canonical_symmetry` picks for the current board; the
+    /// earlier boards fed to the network follow it. Needs one inference, where averaging over all
+    /// symmetries needs eight. Ties go to the lowest index in the canonical orientation, and the
+    /// output cache isn't used.
+    pub fn next_move_canonical(&self, game: &Game) -> Result<usize, AgentError> {
+        let symmetry = game.board().canonical_symmetry();
+        let board = game.board().apply_symmetry(symmetry);
+        let boards = generate_history_boards(game.turn(), game)
+            .map(|(turn, board)| (turn, board.apply_symmetry(symmetry)));
+
+        let model = self.model_for(game.turn());
+        let input = encode_batched_board(&[&boards], model.config())
+            .to_device(self.var_store_for(game.turn()).device());
+        let output = model.forward_t(&input, false).to_device(Device::Cpu);
+        let values: Vec<f32> = output.flatten(0, -1).try_into()?;
+
+        let allowed = allowed_moves(&board, self.candidate_distance);
+
+        let mut best: Option<(usize, f32)> = None;
+        for index in allowed {
+            if best.is_none_or(|(_, value)| values[index] > value) {
+                best = Some((index, values[index]));
+            }
+        }
+
+        let (index, _) = best.ok_or(AgentError::NoLegalMoves)?;
+        Ok(symmetry.inverse().map_index(index, game.board_size()))
+    }
+
+    /// Plays `games` games against itself on a 15×15 board and counts how often each cell was
+    /// played, to see whether the agent favors or ignores parts of the board such as the center.
+    pub fn move_heatmap(&mut self, games: usize) -> Result<Vec<usize>, AgentError> {
+        eval::move_heatmap(self, Opponent::SelfPlay, games)
+    }
+
+    /// Evaluates the Q-values of the given moves, as a `[1, moves]` tensor on the CPU.
+    fn legal_move_values(&self, game: &Game, legal_moves: &[usize]) -> Tensor {
+        let model = self.model_for(game.turn());
+        let boards = generate_history_boards(game.turn(), game);
+        let input = encode_batched_board(&[&boards], model.config())
+            .to_device(self.var_store_for(game.turn()).device());
+        let output = model.forward_t(&input, false).to_device(Device::Cpu);
+
+        let legal_move_indices =
+            Tensor::from_slice(&legal_moves.iter().map(|m| *m as i64).collect::<Vec<_>>());
+        output.index_select(1, &legal_move_indices)
+    }
+}
+
+/// Returns the moves the agent may choose from on `board`: the candidates within
+/// `candidate_distance` of a stone, or every legal move if unrestricted or if no candidate is left,
+/// e.g. with a distance of zero or once the neighborhoods of all stones are filled.
+pub(super) fn allowed_moves(board: &Board, candidate_distance: Option<usize>) -> Vec<usize> {
+    match candidate_distance.map(|distance| board.candidate_moves(distance)) {
+        Some(candidates) if !candidates.is_empty() => candidates,
+        _ => board.legal_moves(),
+    }
+}
+
+/// Computes the entropy, in nats, of the softmax over `values`.
+fn softmax_entropy(values: &[f32]) -> f32 {
+    // shift by the maximum so the exponentials can't overflow
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let weights = Vec::from_iter(values.iter().map(|value| (value - max).exp()));
+    let total = weights.iter().sum::<f32>();
+
+    weights
+        .iter()
+        .map(|weight| weight / total)
+        .filter(|&p| 0.0 < p)
+        .map(|p| -p * p.ln())
+        .sum()
+}
+
+/// The outcome of `GomokuDDQNAgent::load_migrating`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// The config saved next to the weights, if any.
+    pub saved_config: Option<ModelConfig>,
+    /// Whether `saved_config` differs from the agent's config.
+    pub config_changed: bool,
+    /// The variables loaded from the saved weights, sorted by name.
+    pub migrated: Vec<String>,
+    /// The variables missing from the saved weights or saved with another shape, which keep their
+    /// fresh initialization. Sorted by name.
+    pub reinitialized: Vec<String>,
+}
+
+/// Loads the weights with the variables temporarily moved to the CPU, then moves them back.
+///
+/// Tensors saved on another device are mapped to the CPU while being read, so `load` never
+/// depends on the device the weights were saved from.
+fn load_on_cpu(
+    var_store: &mut VarStore,
+    load: impl FnOnce(&mut VarStore) -> Result<(), TchError>,
+) -> Result<(), AgentError> {
+    let device = var_store.device();
+    var_store.set_device(Device::Cpu);
+    let result = load(var_store);
+    var_store.set_device(device);
+    result?;
+    Ok(())
 }
 
 impl Agent for GomokuDDQNAgent {
-    fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn save(&self, path: &str) -> Result<(), AgentError> {
         self.var_store.save(path)?;
+
+        let config = toml::to_string(self.model.config()).map_err(io::Error::other)?;
+        fs::write(Self::config_path(path), config)?;
+
+        let white_path = Self::white_path(path);
+
+        match &self.white {
+            Some((var_store, _)) => var_store.save(&white_path)?,
+            // a white network left over from an earlier save would be loaded back with this one
+            None if Path::new(&white_path).exists() => fs::remove_file(&white_path)?,
+            None => {}
+        }
+
         Ok(())
     }
 
-    fn load(&mut self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
-        self.var_store.load(path)?;
-        Ok(())
+    fn load(&mut self, path: &str) -> Result<(), AgentError> {
+        self.cache.clear();
+
+        // tch reports a missing file as an opaque torch error, so check it upfront
+        fs::metadata(path)?;
+
+        let white_path = Self::white_path(path);
+
+        // weights saved with separate color networks bring their own white network
+        if Path::new(&white_path).exists() {
+            self.enable_separate_color_nets();
+        }
+
+        if let Some((var_store, _)) = &mut self.white {
+            fs::metadata(&white_path)?;
+            load_on_cpu(var_store, |var_store| var_store.load(&white_path))?;
+        }
+
+        load_on_cpu(&mut self.var_store, |var_store| var_store.load(path))
     }
 
-    fn train(
-        &mut self,
-        epoch: usize,
-        options: Figment,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut trainer = GomokuDDQNTrainer;
-        trainer.train(self, epoch, options)?;
-        Ok(())
+    fn to_bytes(&self) -> Result<Vec<u8>, AgentError> {
+        if self.white.is_some() {
+            return Err(AgentError::Unsupported {
+                operation: "to_bytes with separate color networks",
+            });
+        }
+
+        let mut bytes = vec![];
+        self.var_store.save_to_stream(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn from_bytes(&mut self, bytes: &[u8]) -> Result<(), AgentError> {
+        self.cache.clear();
+
+        if self.white.is_some() {
+            return Err(AgentError::Unsupported {
+                operation: "from_bytes with separate color networks",
+            });
+        }
+
+        load_on_cpu(&mut self.var_store, |var_store| {
+            var_store.load_from_stream(Cursor::new(bytes))
+        })
+    }
+
+    fn train(&mut self, epoch: usize, options: Figment) -> Result<(), AgentError> {
+        // the trainer may restrict the agent's moves for the duration of training only
+        let candidate_distance = self.candidate_distance;
+        let mut trainer = GomokuDDQNTrainer::new(std::mem::take(&mut self.loggers));
+        let result = trainer.train(self, epoch, options);
+        self.loggers = trainer.into_loggers();
+        self.candidate_distance = candidate_distance;
+        self.cache.clear();
+        result
     }
 
-    fn next_move(&mut self, game: &Game) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+        if game.board().legal_moves().is_empty() {
+            return Err(AgentError::NoLegalMoves);
+        }
+
         let boards = generate_history_boards(game.turn(), game);
-        let input = encode_batched_board(&[&boards]).to_device(self.var_store.device());
-        let output = self.model.forward_t(&input, false).to_device(Device::Cpu);
+        let key = position_key(&boards);
 
-        // filter-out illegal moves
-        let legal_moves = game.board().legal_moves();
-        let legal_move_indices =
-            Tensor::from_slice(&legal_moves.iter().map(|m| *m as i64).collect::<Vec<_>>());
-        let legal_q_values = output.index_select(1, &legal_move_indices);
-        let index = legal_q_values.argmax(1, false).int64_value(&[0]);
+        let mut values = match self
+            .cache
+            .is_enabled()
+            .then(|| self.cache.get(&key))
+            .flatten()
+        {
+            Some(values) => values.to_vec(),
+            None => {
+                let model = self.model_for(game.turn());
+                let input = encode_batched_board(&[&boards], model.config())
+                    .to_device(self.var_store_for(game.turn()).device());
+                let output = model.forward_t(&input, false).to_device(Device::Cpu);
+                let values: Vec<f32> = output.flatten(0, -1).try_into()?;
+
+                if self.cache.is_enabled() {
+                    self.cache.insert(key, values.clone());
+                }
+
+                values
+            }
+        };
+
+        // filter-out illegal moves, and far-flung ones if restricted
+        let mut allowed = vec![false; values.len()];
+        for index in allowed_moves(game.board(), self.candidate_distance) {
+            allowed[index] = true;
+        }
+
+        for (value, allowed) in values.iter_mut().zip(allowed) {
+            if !allowed {
+                *value = f32::NEG_INFINITY;
+            }
+        }
+
+        let allowed_values =
+            Vec::from_iter(values.iter().copied().filter(|value| value.is_finite()));
+        self.last_policy_entropy = Some(softmax_entropy(&allowed_values));
+
+        select_move(
+            &values,
+            game.board_size(),
+            self.tie_break,
+            &mut self.tie_break_rng,
+        )
+        .ok_or(AgentError::NoLegalMoves)
+    }
+
+    fn reset(&mut self) {
+        self.cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::gomoku_ddqn::{EncodingMode, NormKind};
+    use gomoku_core::board::Symmetry;
+
+    fn fill_board_without_winner(game: &mut Game) {
+        let board_size = game.board_size();
+        let (black, white): (Vec<_>, Vec<_>) = (0..board_size * board_size)
+            .partition(|&index| ((index % board_size) / 2 + index / board_size).is_multiple_of(2));
+
+        for (i, &index) in black.iter().enumerate() {
+            game.place_stone(index).unwrap();
+
+            if let Some(&index) = white.get(i) {
+                game.place_stone(index).unwrap();
+            }
+        }
+    }
+
+    fn small_agent() -> GomokuDDQNAgent {
+        GomokuDDQNAgent::new(ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        })
+    }
+
+    #[test]
+    fn test_softmax_entropy() {
+        let uniform = softmax_entropy(&[0.5; 100]);
+        assert!((uniform - (100f32).ln()).abs() < 1e-4);
+
+        let peaked = softmax_entropy(&[100.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(peaked < 1e-4);
+
+        let mixed = softmax_entropy(&[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(peaked < mixed && mixed < (8f32).ln());
+    }
+
+    #[test]
+    fn test_policy_entropy() {
+        let agent = small_agent();
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+
+        let entropy = agent.policy_entropy(&game);
+        assert!((0.0..=(224f32).ln() + 1e-4).contains(&entropy));
+
+        let mut game = Game::new(15, 5);
+        fill_board_without_winner(&mut game);
+        assert_eq!(agent.policy_entropy(&game), 0.0);
+    }
+
+    #[test]
+    fn test_take_policy_entropy() {
+        let mut agent = small_agent();
+        assert_eq!(agent.take_policy_entropy(), None);
+
+        let game = Game::from_moves(15, 5, &[112]).unwrap();
+        agent.next_move(&game).unwrap();
+
+        let entropy = agent.take_policy_entropy().unwrap();
+        assert!((entropy - agent.policy_entropy(&game)).abs() < 1e-4);
+        assert_eq!(agent.take_policy_entropy(), None);
+    }
+
+    #[test]
+    fn test_next_move_on_full_board() {
+        let mut agent = small_agent();
+        let mut game = Game::new(15, 5);
+        fill_board_without_winner(&mut game);
+
+        assert!(game.board().legal_moves().is_empty());
+        assert!(matches!(
+            agent.next_move(&game),
+            Err(AgentError::NoLegalMoves)
+        ));
+    }
+
+    #[test]
+    fn test_next_move_canonical() {
+        let agent = small_agent();
+        let moves = [17, 18, 50, 64, 200];
+
+        let mut game = Game::new(15, 5);
+        for index in moves {
+            game.place_stone(index).unwrap();
+        }
+
+        let index = agent.next_move_canonical(&game).unwrap();
+        assert!(game.board().legal_moves().contains(&index));
+
+        for symmetry in Symmetry::ALL {
+            let mut transformed = Game::new(15, 5);
+            for index in moves {
+                transformed
+                    .place_stone(symmetry.map_index(index, 15))
+                    .unwrap();
+            }
+
+            assert_eq!(
+                agent.next_move_canonical(&transformed).unwrap(),
+                symmetry.map_index(index, 15),
+                "{:?}",
+                symmetry
+            );
+        }
+
+        let mut game = Game::new(15, 5);
+        fill_board_without_winner(&mut game);
+        assert!(matches!(
+            agent.next_move_canonical(&game),
+            Err(AgentError::NoLegalMoves)
+        ));
+    }
+
+    #[test]
+    fn test_opening_report() {
+        let agent = small_agent();
+        let report = agent.opening_report(5, 3);
+
+        assert_eq!(report.entries.len(), 4);
+        assert!(report.entries[0].moves.is_empty());
+
+        for entry in &report.entries {
+            let game = Game::from_moves(15, 5, &entry.moves).unwrap();
+
+            assert_eq!(entry.top_moves.len(), 3);
+            assert!(entry
+                .top_moves
+                .windows(2)
+                .all(|pair| pair[0].1 >= pair[1].1));
+
+            for &(index, value) in &entry.top_moves {
+                assert!(game.board().get_cell(index).unwrap().is_empty());
+                assert!(value.is_finite());
+            }
+        }
+
+        assert!(agent.opening_report(16, 3).entries.is_empty());
+    }
+
+    #[test]
+    fn test_load_missing_path() {
+        let mut agent = small_agent();
+
+        assert!(matches!(
+            agent.load("this/path/does/not/exist.safetensors"),
+            Err(AgentError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_move_win_probs() {
+        let agent = small_agent();
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+        game.place_stone(113).unwrap();
+
+        let probs = agent.move_win_probs(&game);
+        let moves = probs.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+
+        assert_eq!(moves, game.board().legal_moves());
+        assert!(probs.iter().all(|(_, prob)| (0.0..=1.0).contains(prob)));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let agent = small_agent();
+        let mut restored = small_agent();
+
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+
+        let boards = generate_history_boards(game.turn(), &game);
+        let input = encode_batched_board(&[&boards], agent.model().config());
+
+        restored.from_bytes(&agent.to_bytes().unwrap()).unwrap();
+
+        let expected = agent.model().forward_t(&input, false);
+        let actual = restored.model().forward_t(&input, false);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_save_load_cpu() {
+        let config = small_agent().model().config().clone();
+        let agent = GomokuDDQNAgent::with_device(config.clone(), Device::Cpu);
+        let mut restored = GomokuDDQNAgent::with_device(config, Device::Cpu);
+
+        let path = std::env::temp_dir().join(format!(
+            "gomoku-ddqn-save-load-{}.safetensors",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        agent.save(path).unwrap();
+        let result = restored.load(path);
+        fs::remove_file(path).unwrap();
+        fs::remove_file(GomokuDDQNAgent::config_path(path)).unwrap();
+        result.unwrap();
+
+        assert_eq!(restored.var_store().device(), Device::Cpu);
+
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+
+        let boards = generate_history_boards(game.turn(), &game);
+        let input = encode_batched_board(&[&boards], agent.model().config());
+
+        let expected = agent.model().forward_t(&input, false);
+        let actual = restored.model().forward_t(&input, false);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_save_load_separate_color_nets() {
+        let config = small_agent().model().config().clone();
+        let mut agent = GomokuDDQNAgent::with_device(config.clone(), Device::Cpu);
+        agent.enable_separate_color_nets();
+
+        // make the white network differ from the black one it started as
+        tch::no_grad(|| {
+            for (_, mut variable) in agent.var_store_for(Turn::White).variables() {
+                let _ = variable.g_add_scalar_(1.0);
+            }
+        });
+
+        let path = std::env::temp_dir().join(format!(
+            "gomoku-ddqn-save-load-white-{}.safetensors",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        agent.save(path).unwrap();
+        let mut restored = GomokuDDQNAgent::with_device(config, Device::Cpu);
+        let result = restored.load(path);
+        fs::remove_file(path).unwrap();
+        fs::remove_file(GomokuDDQNAgent::white_path(path)).unwrap();
+        fs::remove_file(GomokuDDQNAgent::config_path(path)).unwrap();
+        result.unwrap();
+
+        assert!(restored.has_separate_color_nets());
+
+        for turn in [Turn::Black, Turn::White] {
+            let saved = agent.var_store_for(turn).variables();
+            let loaded = restored.var_store_for(turn).variables();
+
+            assert_eq!(saved.len(), loaded.len());
+            for (name, tensor) in &saved {
+                assert_eq!(&loaded[name], tensor, "{:?} {}", turn, name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_separate_color_nets_routing() {
+        let mut agent = small_agent();
+        assert!(std::ptr::eq(agent.model_for(Turn::White), agent.model()));
+
+        agent.enable_separate_color_nets();
+
+        let mut game = Game::new(15, 5);
+        assert_eq!(game.turn(), Turn::Black);
+        assert!(std::ptr::eq(agent.model_for(game.turn()), agent.model()));
+
+        game.place_stone(112).unwrap();
+        assert_eq!(game.turn(), Turn::White);
+        assert!(!std::ptr::eq(agent.model_for(game.turn()), agent.model()));
+        assert!(!std::ptr::eq(
+            agent.var_store_for(game.turn()),
+            agent.var_store()
+        ));
+    }
+
+    #[test]
+    fn test_output_cache() {
+        let mut agent = small_agent();
+        agent.set_cache_capacity(16);
+        assert_eq!(agent.cache_capacity(), 16);
+
+        let game = Game::from_moves(15, 5, &[112]).unwrap();
+        let first = agent.next_move(&game).unwrap();
+        assert_eq!((agent.cache_hits(), agent.cache_misses()), (0, 1));
+
+        assert_eq!(agent.next_move(&game).unwrap(), first);
+        assert_eq!((agent.cache_hits(), agent.cache_misses()), (1, 1));
+
+        let other = Game::from_moves(15, 5, &[113]).unwrap();
+        agent.next_move(&other).unwrap();
+        assert_eq!((agent.cache_hits(), agent.cache_misses()), (1, 2));
+
+        agent.reset();
+        agent.next_move(&game).unwrap();
+        assert_eq!((agent.cache_hits(), agent.cache_misses()), (1, 3));
+    }
+
+    #[test]
+    fn test_load_migrating() {
+        let config = small_agent().model().config().clone();
+        let agent = GomokuDDQNAgent::with_device(config.clone(), Device::Cpu);
+        let mut migrated = GomokuDDQNAgent::with_device(
+            ModelConfig {
+                fc0_channels: 16,
+                ..config.clone()
+            },
+            Device::Cpu,
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "gomoku-ddqn-load-migrating-{}.safetensors",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        agent.save(path).unwrap();
+        let report = migrated.load_migrating(path);
+        fs::remove_file(path).unwrap();
+        fs::remove_file(GomokuDDQNAgent::config_path(path)).unwrap();
+        let report = report.unwrap();
+
+        assert_eq!(report.saved_config, Some(config));
+        assert!(report.config_changed);
+
+        // only the two FC layers around `fc0_channels` change shape
+        let saved = agent.var_store().variables();
+        let loaded = migrated.var_store().variables();
+        let mut changed = loaded
+            .iter()
+            .filter(|(name, tensor)| saved[*name].size() != tensor.size())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        changed.sort();
+
+        assert_eq!(report.reinitialized, changed);
+        assert!(!report.reinitialized.is_empty());
+        assert_eq!(report.migrated.len() + changed.len(), loaded.len());
+
+        for name in &report.migrated {
+            assert_eq!(saved[name], loaded[name]);
+        }
+    }
+
+    #[test]
+    fn test_self_play() {
+        let agent = small_agent();
+
+        let (moves, result) = agent.self_play(15, 5, 42);
+        let (same_moves, same_result) = agent.self_play(15, 5, 42);
+
+        assert_eq!(moves, same_moves);
+        assert_eq!(result, same_result);
+
+        let game = Game::from_moves(15, 5, &moves).unwrap();
+        assert_eq!(game.game_result(), Some(result));
+    }
+
+    #[test]
+    fn test_candidate_distance() {
+        let mut agent = small_agent();
+        agent.set_candidate_distance(Some(1));
+
+        let mut game = Game::new(15, 5);
+        game.place_stone(0).unwrap();
+
+        while game.game_result().is_none() && game.turn_count() < 60 {
+            let candidates = game.board().candidate_moves(1);
+            let index = agent.next_move(&game).unwrap();
+
+            assert!(
+                candidates.contains(&index),
+                "{} is far from any stone",
+                index
+            );
+            game.place_stone(index).unwrap();
+        }
 
-        Ok(legal_moves[index as usize])
+        // training restores the restriction the agent had before
+        agent.set_candidate_distance(None);
+        let options = Figment::new().merge(Toml::string(
+            "iterations = 1\ntraining_steps = 1\neval_games = 0\nrestrict_to_candidates = true",
+        ));
+        agent.train(1, options).unwrap();
+        assert_eq!(agent.candidate_distance(), None);
     }
 }
@@ -0,0 +1,222 @@
+use gomoku_core::{board::Board, game::Turn};
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+/// The frames fed to the network, used as a cache key.
+///
+/// Keys are compared frame by frame, so two positions whose hashes collide never share an entry.
+/// Symmetric positions get different keys, since the network output depends on the orientation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionKey([(Turn, Board); 4]);
+
+impl Hash for PositionKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (turn, board) in &self.0 {
+            turn.hash(state);
+            board.board_size().hash(state);
+            board.cells().hash(state);
+        }
+    }
+}
+
+pub fn position_key(boards: &[(Turn, Board); 4]) -> PositionKey {
+    PositionKey(boards.clone())
+}
+
+/// A least-recently-used cache of network outputs, keyed by `PositionKey`.
+///
+/// A capacity of zero disables the cache. Lookups and insertions take amortized constant time:
+/// using an entry stamps it and queues the stamp, and stamps an entry has outlived are skipped when
+/// evicting.
+#[derive(Debug)]
+pub struct OutputCache<K = PositionKey> {
+    capacity: usize,
+    /// Each entry's output and the stamp of its latest use.
+    entries: HashMap<Rc<K>, (Vec<f32>, u64)>,
+    /// Uses from the least to the most recent, including outdated ones.
+    order: VecDeque<(u64, Rc<K>)>,
+    next_stamp: u64,
+    hits: usize,
+    misses: usize,
+}
+
+impl<K> Default for OutputCache<K> {
+    fn default() -> Self {
+        Self {
+            capacity: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            next_stamp: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq> OutputCache<K> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Looks up an output, counting a hit or a miss.
+    pub fn get(&mut self, key: &K) -> Option<&[f32]> {
+        let Some((key, _)) = self.entries.get_key_value(key) else {
+            self.misses += 1;
+            return None;
+        };
+
+        self.hits += 1;
+        let key = key.clone();
+        self.touch(&key);
+        self.entries.get(&key).map(|(values, _)| values.as_slice())
+    }
+
+    pub fn insert(&mut self, key: K, values: Vec<f32>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let key = Rc::new(key);
+        if let Some((old_values, _)) = self.entries.get_mut(&key) {
+            *old_values = values;
+            self.touch(&key);
+            return;
+        }
+
+        self.entries.insert(key.clone(), (values, 0));
+        self.touch(&key);
+
+        while self.capacity < self.entries.len() {
+            let Some((stamp, evicted)) = self.order.pop_front() else {
+                break;
+            };
+
+            if self
+                .entries
+                .get(&evicted)
+                .is_some_and(|&(_, last)| last == stamp)
+            {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    /// Drops every entry, keeping the capacity and the counters.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Marks the entry of `key` as the most recently used.
+    fn touch(&mut self, key: &Rc<K>) {
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+
+        if let Some((_, last)) = self.entries.get_mut(key) {
+            *last = stamp;
+        }
+        self.order.push_back((stamp, key.clone()));
+
+        // drop the outdated uses once they outnumber the entries, so the queue stays bounded
+        if 2 * self.entries.len().max(self.capacity) < self.order.len() {
+            let entries = &self.entries;
+            self.order
+                .retain(|(stamp, key)| entries.get(key).is_some_and(|&(_, last)| last == *stamp));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gomoku_core::board::Cell;
+
+    #[test]
+    fn test_output_cache_lru() {
+        let mut cache = OutputCache::new(2);
+        cache.insert(1, vec![1.0]);
+        cache.insert(2, vec![2.0]);
+
+        // 1 becomes the most recently used, so 2 is evicted next
+        assert_eq!(cache.get(&1), Some([1.0].as_slice()));
+        cache.insert(3, vec![3.0]);
+
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some([1.0].as_slice()));
+        assert_eq!(cache.get(&3), Some([3.0].as_slice()));
+        assert_eq!((cache.hits(), cache.misses()), (3, 1));
+
+        cache.clear();
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.capacity(), 2);
+    }
+
+    #[test]
+    fn test_output_cache_many_hits() {
+        let mut cache = OutputCache::new(2);
+        cache.insert(1, vec![1.0]);
+        cache.insert(2, vec![2.0]);
+
+        for _ in 0..100 {
+            cache.get(&1);
+        }
+        assert!(cache.order.len() <= 4);
+
+        // reinserting refreshes the entry
+        cache.insert(2, vec![4.0]);
+        cache.insert(3, vec![3.0]);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some([4.0].as_slice()));
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_disabled_output_cache() {
+        let mut cache = OutputCache::new(0);
+        cache.insert(1, vec![1.0]);
+
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn test_position_key() {
+        let empty = std::array::from_fn(|_| (Turn::Black, Board::new(15)));
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        let mut moved = empty.clone();
+        moved[3] = (Turn::Black, board);
+
+        assert_eq!(position_key(&empty), position_key(&empty.clone()));
+        assert_ne!(position_key(&empty), position_key(&moved));
+
+        let white = std::array::from_fn(|_| (Turn::White, Board::new(15)));
+        assert_ne!(position_key(&empty), position_key(&white));
+
+        let mut cache = OutputCache::new(2);
+        cache.insert(position_key(&empty), vec![1.0]);
+        assert_eq!(cache.get(&position_key(&moved)), None);
+        assert_eq!(cache.get(&position_key(&empty)), Some([1.0].as_slice()));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{agent::AgentError, replay::Opponent, summary::EventWriter};
+use gomoku_core::game::{Game, Turn};
+use std::{fmt::Debug, io, path::Path};
+
+/// The statistics of a finished training epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochReport {
+    /// The zero-based epoch.
+    pub epoch: usize,
+    /// The mean loss of the recent training steps.
+    pub loss: f64,
+    /// The mean entropy of the agent's policy over the positions it played during the epoch.
+    pub policy_entropy: f64,
+    pub epsilon: f64,
+    /// The distinct positions in the replay buffer; see `count_unique_positions`.
+    pub unique_positions: usize,
+    pub replay_buffer_len: usize,
+    pub agent_wins: usize,
+    pub opponent_wins: usize,
+    pub draws: usize,
+    /// The evaluation games the agent won while playing black, out of the `black_games` it played
+    /// as black; likewise for white.
+    pub black_wins: usize,
+    pub black_games: usize,
+    pub white_wins: usize,
+    pub white_games: usize,
+    pub mean_game_length: f64,
+    /// The accuracy on the probe set, if one is given.
+    pub probe_accuracy: Option<f64>,
+}
+
+impl EpochReport {
+    /// The fraction of evaluation games the agent won, or zero if none were played.
+    pub fn win_rate(&self) -> f64 {
+        let games = self.agent_wins + self.opponent_wins + self.draws;
+
+        if games == 0 {
+            0.0
+        } else {
+            self.agent_wins as f64 / games as f64
+        }
+    }
+}
+
+/// The statistics of a single optimizer step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepReport {
+    /// The zero-based epoch.
+    pub epoch: usize,
+    /// The zero-based training step within the epoch.
+    pub step: usize,
+    pub loss: f64,
+}
+
+/// A game against the current opponent, played after an epoch to show how the agent plays.
+#[derive(Debug, Clone)]
+pub struct RecentGameReport {
+    /// The zero-based epoch.
+    pub epoch: usize,
+    pub agent_turn: Turn,
+    pub game: Game,
+    /// The moves of the game in order, as `format_move` writes them.
+    pub moves: Vec<String>,
+}
+
+/// Receives the trainer's reports, e.g. to print or record them.
+pub trait TrainLogger: Debug {
+    fn on_epoch(&mut self, report: &EpochReport);
+    fn on_step(&mut self, _report: &StepReport) {}
+    /// Called when the curriculum moves on to the next opponent after the given epoch.
+    fn on_curriculum_advanced(&mut self, _epoch: usize, _opponent: Opponent) {}
+    fn on_recent_game(&mut self, _report: &RecentGameReport) {}
+    /// Called when saving the agent after an epoch fails. Training goes on regardless.
+    fn on_save_failed(&mut self, _path: &str, _err: &AgentError) {}
+}
+
+/// Prints a summary of every epoch to stdout. This is the agent's default logger.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutLogger;
+
+impl TrainLogger for StdoutLogger {
+    fn on_epoch(&mut self, report: &EpochReport) {
+        println!("epoches: {}", report.epoch + 1);
+        println!("loss: {}", report.loss);
+        println!("policy entropy: {}", report.policy_entropy);
+        println!(
+            "unique positions: {} / {}",
+            report.unique_positions, report.replay_buffer_len
+        );
+        println!(
+            "agent wins: {}, opponent wins: {}, draws: {}, mean game length: {:.1}",
+            report.agent_wins, report.opponent_wins, report.draws, report.mean_game_length
+        );
+        println!(
+            "agent wins as black: {} / {}, as white: {} / {}",
+            report.black_wins, report.black_games, report.white_wins, report.white_games
+        );
+
+        if let Some(probe_accuracy) = report.probe_accuracy {
+            println!("probe accuracy: {}", probe_accuracy);
+        }
+    }
+
+    fn on_curriculum_advanced(&mut self, _epoch: usize, opponent: Opponent) {
+        println!("curriculum advanced to {:?}", opponent);
+    }
+
+    fn on_recent_game(&mut self, report: &RecentGameReport) {
+        println!(
+            "recent game [agent={}]:\n{}",
+            report.agent_turn.name(),
+            report.game
+        );
+        println!("moves: {}", report.moves.join(", "));
+    }
+
+    fn on_save_failed(&mut self, path: &str, err: &AgentError) {
+        eprintln!("failed to save agent to {}: {:#?}", path, err);
+    }
+}
+
+/// Writes the loss, win-rate, epsilon, policy entropy and probe accuracy, if any, of every epoch to
+/// TensorBoard event files.
+#[derive(Debug)]
+pub struct TensorBoardLogger {
+    event_writer: EventWriter,
+}
+
+impl TensorBoardLogger {
+    pub fn new(logdir: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            event_writer: EventWriter::new(logdir)?,
+        })
+    }
+}
+
+impl TrainLogger for TensorBoardLogger {
+    fn on_epoch(&mut self, report: &EpochReport) {
+        let event_writer = &mut self.event_writer;
+        let step = report.epoch as i64;
+        let result = event_writer
+            .add_scalar("loss", report.loss as f32, step)
+            .and_then(|_| event_writer.add_scalar("win_rate", report.win_rate() as f32, step))
+            .and_then(|_| event_writer.add_scalar("epsilon", report.epsilon as f32, step))
+            .and_then(|_| {
+                event_writer.add_scalar("policy_entropy", report.policy_entropy as f32, step)
+            })
+            .and_then(|_| match report.probe_accuracy {
+                Some(probe_accuracy) => {
+                    event_writer.add_scalar("probe_accuracy", probe_accuracy as f32, step)
+                }
+                None => Ok(()),
+            })
+            .and_then(|_| event_writer.flush());
+
+        if let Err(err) = result {
+            eprintln!("failed to write events: {:#?}", err);
+        }
+    }
+}
+
+/// Redraws a PNG plot of the per-epoch loss and win-rate curves after every epoch.
+#[derive(Debug)]
+pub struct PlotLogger {
+    path: String,
+    curves: training_curves::TrainingCurves,
+}
+
+impl PlotLogger {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            curves: training_curves::TrainingCurves::new(),
+        }
+    }
+}
+
+impl TrainLogger for PlotLogger {
+    fn on_epoch(&mut self, report: &EpochReport) {
+        self.curves.add(report.loss, report.win_rate());
+
+        if let Err(err) = self.curves.render(&self.path, 1024, 768) {
+            eprintln!("failed to plot training curves: {:#?}", err);
+        }
+    }
+}
+
+mod training_curves {
+    use plotters::prelude::*;
+    use std::{error::Error, path::Path};
+
+    /// The per-epoch loss and win-rate series of a training run.
+    #[derive(Debug)]
+    pub struct TrainingCurves {
+        losses: Vec<f64>,
+        win_rates: Vec<f64>,
+    }
+
+    impl TrainingCurves {
+        pub fn new() -> Self {
+            Self {
+                losses: vec![],
+                win_rates: vec![],
+            }
+        }
+
+        pub fn add(&mut self, loss: f64, win_rate: f64) {
+            self.losses.push(loss);
+            self.win_rates.push(win_rate);
+        }
+
+        /// Draws the loss (top) and win-rate (bottom) curves into a PNG image.
+        pub fn render(
+            &self,
+            path: impl AsRef<Path>,
+            width: u32,
+            height: u32,
+        ) -> Result<(), Box<dyn Error>> {
+            let root = BitMapBackend::new(path.as_ref(), (width, height)).into_drawing_area();
+            root.fill(&WHITE)?;
+
+            let (upper, lower) = root.split_vertically(height / 2);
+            let max_loss = self.losses.iter().copied().fold(0.0, f64::max);
+
+            draw_curve(&upper, "loss", &self.losses, 0.0..max_loss.max(1e-6), &RED)?;
+            draw_curve(&lower, "win rate", &self.win_rates, 0.0..1.0, &BLUE)?;
+
+            root.present()?;
+            Ok(())
+        }
+    }
+
+    fn draw_curve(
+        area: &DrawingArea<BitMapBackend, plotters::coord::Shift>,
+        name: &str,
+        values: &[f64],
+        range: std::ops::Range<f64>,
+        color: &RGBColor,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut chart = ChartBuilder::on(area)
+            .caption(name, ("sans-serif", 24))
+            .margin(12)
+            .x_label_area_size(32)
+            .y_label_area_size(48)
+            .build_cartesian_2d(0..values.len().max(1), range)?;
+
+        chart.configure_mesh().x_desc("epoch").y_desc(name).draw()?;
+        chart.draw_series(LineSeries::new(values.iter().copied().enumerate(), color))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_training_curves_png() {
+        let mut curves = training_curves::TrainingCurves::new();
+        for epoch in 0..20 {
+            curves.add(1.0 / (epoch + 1) as f64, epoch as f64 / 20.0);
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("gomoku-training-curves-{}.png", std::process::id()));
+        curves.render(&path, 640, 480).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // the PNG signature, then the width and height from the IHDR chunk
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(u32::from_be_bytes(bytes[16..20].try_into().unwrap()), 640);
+        assert_eq!(u32::from_be_bytes(bytes[20..24].try_into().unwrap()), 480);
+    }
+
+    #[test]
+    fn test_win_rate() {
+        let report = EpochReport {
+            epoch: 0,
+            loss: 0.0,
+            policy_entropy: 0.0,
+            epsilon: 0.0,
+            unique_positions: 0,
+            replay_buffer_len: 0,
+            agent_wins: 3,
+            opponent_wins: 4,
+            draws: 1,
+            black_wins: 2,
+            black_games: 4,
+            white_wins: 1,
+            white_games: 4,
+            mean_game_length: 0.0,
+            probe_accuracy: None,
+        };
+        assert_eq!(report.win_rate(), 0.375);
+
+        let report = EpochReport {
+            agent_wins: 0,
+            opponent_wins: 0,
+            draws: 0,
+            ..report
+        };
+        assert_eq!(report.win_rate(), 0.0);
+    }
+}
@@ -1,23 +1,265 @@
-use crate::nn_utils::{
-    copy_weights_batch_norm2d, copy_weights_conv2d, copy_weights_linear,
-    copy_weights_residual_block, residual_block, ResidualBlock,
+use crate::{
+    agent::AgentError,
+    nn_utils::{
+        copy_stats_batch_norm2d, copy_stats_norm, copy_stats_residual_block,
+        copy_weights_batch_norm2d, copy_weights_conv2d, copy_weights_linear, copy_weights_norm,
+        copy_weights_residual_block, norm2d, residual_block, Norm, NormKind, ResidualBlock,
+    },
 };
+use figment::Figment;
 use gomoku_core::{
-    board::{Board, Cell},
+    board::{Board, Cell, Symmetry},
     game::Turn,
 };
+use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use tch::{
-    nn::{batch_norm2d, conv2d, linear, BatchNorm, Conv2D, ConvConfig, Linear, ModuleT, Path},
-    no_grad, Device, Tensor,
+    nn::{
+        batch_norm2d, conv2d, linear, BatchNorm, BatchNormConfig, Conv2D, ConvConfig, Linear,
+        ModuleT, Path,
+    },
+    no_grad, Device, Kind, Tensor,
 };
+use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub board_size: usize,
     pub residual_blocks: usize,
-    pub residual_block_channels: usize,
+    pub residual_block_channels: ResidualChannels,
+    /// Groups the residual blocks into stages. `None` keeps them in a single stage.
+    #[serde(default)]
+    pub stages: Option<Stages>,
     pub fc0_channels: usize,
+    /// Whether each encoded frame starts with a plane filled with the player to move.
+    pub include_turn_plane: bool,
+    /// Whether each encoded frame ends with a plane marking the legal moves.
+    pub include_legal_plane: bool,
+    /// Whether the input planes are standardized per channel by a batch norm without affine
+    /// parameters, so planes of different scales reach the network as zero-mean and unit-variance.
+    #[serde(default)]
+    pub normalize_input: bool,
+    /// The normalization after the channel-matching conv and the convs of the residual blocks.
+    #[serde(default)]
+    pub norm: NormKind,
+    pub encoding: EncodingMode,
+}
+
+impl ModelConfig {
+    /// Extracts a config and checks it with `validate`, so that a bad one is reported as a config
+    /// error rather than a panic when building the model.
+    pub fn from_figment(figment: &Figment) -> Result<Self, AgentError> {
+        let config: Self = figment.extract()?;
+        config
+            .validate()
+            .map_err(|err| figment::Error::from(err.to_string()))?;
+        Ok(config)
+    }
+
+    /// Checks the parts of the config that deserialize fine but can't be built: stages that are
+    /// empty or don't add up to `residual_blocks`, and per-block widths for another block count.
+    pub fn validate(&self) -> Result<(), ModelConfigError> {
+        if let ResidualChannels::PerBlock(channels) = &self.residual_block_channels {
+            if channels.is_empty() || channels.len() != self.residual_blocks {
+                return Err(ModelConfigError::BlockChannelsMismatch {
+                    widths: channels.len(),
+                    residual_blocks: self.residual_blocks,
+                });
+            }
+        }
+
+        if let Some(stages) = &self.stages {
+            if stages.sizes.is_empty() {
+                return Err(ModelConfigError::NoStages);
+            }
+
+            if let Some(stage) = stages.sizes.iter().position(|&size| size == 0) {
+                return Err(ModelConfigError::EmptyStage { stage });
+            }
+
+            let blocks = stages.sizes.iter().sum::<usize>();
+            if blocks != self.residual_blocks {
+                return Err(ModelConfigError::StageSizeMismatch {
+                    blocks,
+                    residual_blocks: self.residual_blocks,
+                });
+            }
+        }
+
+        if let NormKind::GroupNorm { groups } = self.norm {
+            let input_channels = self.residual_block_channels.input_channels();
+            let block_channels = self
+                .residual_block_channels
+                .block_channels(self.residual_blocks);
+            if let Some(channels) = std::iter::once(input_channels)
+                .chain(block_channels)
+                .find(|&channels| groups == 0 || channels % groups != 0)
+            {
+                return Err(ModelConfigError::IndivisibleGroups { groups, channels });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of planes per encoded `(turn, board)` frame.
+    pub fn frame_channels(&self) -> usize {
+        3 + self.include_turn_plane as usize + self.include_legal_plane as usize
+    }
+
+    /// The side length of the planes leaving the residual tower, after any pooling between stages.
+    pub fn output_size(&self) -> usize {
+        let pools = match &self.stages {
+            Some(stages) if stages.transition == StageTransition::Pool => {
+                stages.sizes.len().saturating_sub(1)
+            }
+            _ => 0,
+        };
+
+        (0..pools).fold(self.board_size, |size, _| size.div_ceil(2))
+    }
+
+    /// Number of planes of the encoded input.
+    pub fn input_channels(&self) -> usize {
+        match self.encoding {
+            EncodingMode::Stacked => 4 * self.frame_channels(),
+            EncodingMode::Recency => self.frame_channels() + 1,
+        }
+    }
+}
+
+/// How the 4 history frames are encoded into the model input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingMode {
+    /// All 4 frames, one after another.
+    #[default]
+    Stacked,
+    /// The latest frame, followed by a single plane telling how recently each stone was placed:
+    /// `1` for stones that appeared in the latest frame, halved for every frame further back.
+    Recency,
+}
+
+/// Groups of consecutive residual blocks, with a transition at the start of every stage but the
+/// first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Stages {
+    /// The number of blocks in each stage, summing to `residual_blocks`.
+    pub sizes: Vec<usize>,
+    #[serde(default)]
+    pub transition: StageTransition,
+}
+
+impl Stages {
+    /// Returns whether each block starts a stage other than the first.
+    pub fn stage_starts(&self, residual_blocks: usize) -> Vec<bool> {
+        assert_eq!(
+            self.sizes.iter().sum::<usize>(),
+            residual_blocks,
+            "stage sizes must sum to the number of residual blocks"
+        );
+
+        let mut starts = vec![false; residual_blocks];
+        let mut start = 0;
+
+        for &size in &self.sizes[..self.sizes.len().saturating_sub(1)] {
+            start += size;
+            starts[start] = true;
+        }
+
+        starts
+    }
+}
+
+/// What happens between two stages of residual blocks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageTransition {
+    /// A 1x1 projection conv, whether or not the width changes.
+    #[default]
+    Projection,
+    /// A 2x2 max pool halving the planes, rounding up. A projection conv is only added if the width
+    /// changes.
+    Pool,
+}
+
+/// Channel widths of the residual blocks.
+///
+/// `Uniform` uses the same width for every block, while `PerBlock` gives each block its own width
+/// (e.g. to taper the network). A 1x1 projection conv is inserted between blocks of differing widths.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResidualChannels {
+    Uniform(usize),
+    PerBlock(Vec<usize>),
+}
+
+impl ResidualChannels {
+    /// Returns the channel width of each block.
+    pub fn block_channels(&self, residual_blocks: usize) -> Vec<usize> {
+        match self {
+            ResidualChannels::Uniform(channels) => vec![*channels; residual_blocks],
+            ResidualChannels::PerBlock(channels) => {
+                assert_eq!(
+                    channels.len(),
+                    residual_blocks,
+                    "per-block channels must match the number of residual blocks"
+                );
+                channels.clone()
+            }
+        }
+    }
+
+    /// Returns the channel width entering the first block.
+    pub fn input_channels(&self) -> usize {
+        match self {
+            ResidualChannels::Uniform(channels) => *channels,
+            ResidualChannels::PerBlock(channels) => channels[0],
+        }
+    }
+
+    /// Returns the channel width leaving the last block.
+    pub fn output_channels(&self) -> usize {
+        match self {
+            ResidualChannels::Uniform(channels) => *channels,
+            ResidualChannels::PerBlock(channels) => channels[channels.len() - 1],
+        }
+    }
+}
+
+impl From<usize> for ResidualChannels {
+    fn from(channels: usize) -> Self {
+        ResidualChannels::Uniform(channels)
+    }
+}
+
+impl From<Vec<usize>> for ResidualChannels {
+    fn from(channels: Vec<usize>) -> Self {
+        ResidualChannels::PerBlock(channels)
+    }
+}
+
+/// Why a `ModelConfig` can't be built; see `ModelConfig::validate`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ModelConfigError {
+    #[error("{widths} per-block channel widths given for {residual_blocks} residual blocks")]
+    BlockChannelsMismatch {
+        widths: usize,
+        residual_blocks: usize,
+    },
+    #[error("stages must have at least one stage")]
+    NoStages,
+    /// The stage at the given zero-based position has no residual blocks.
+    #[error("stage {stage} has no residual blocks")]
+    EmptyStage { stage: usize },
+    #[error("stage sizes sum to {blocks}, but there are {residual_blocks} residual blocks")]
+    StageSizeMismatch {
+        blocks: usize,
+        residual_blocks: usize,
+    },
+    /// A group norm's `groups` doesn't divide the channel width of some layer it normalizes.
+    #[error("{groups} groups don't divide {channels} channels")]
+    IndivisibleGroups { groups: usize, channels: usize },
 }
 
 #[derive(Debug)]
@@ -25,41 +267,76 @@ pub struct Model {
     device: Device,
     config: ModelConfig,
     match_channel_conv: Conv2D,
-    match_channel_bn: BatchNorm,
-    residual_blocks: Vec<ResidualBlock>,
+    match_channel_norm: Norm,
+    /// Residual blocks, each preceded by a 1x1 projection conv if its width differs from the previous one.
+    residual_blocks: Vec<(Option<Conv2D>, ResidualBlock)>,
+    /// Whether each residual block is preceded by a pool, at the start of a pooled stage.
+    pooled: Vec<bool>,
     fc0: Linear,
     fc1: Linear,
+    input_bn: Option<BatchNorm>,
 }
 
 impl Model {
+    /// Builds the model.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the config is invalid; see `ModelConfig::validate`.
     pub fn new<'a>(vs: impl Borrow<Path<'a>>, config: ModelConfig) -> Self {
+        if let Err(err) = config.validate() {
+            panic!("{}", err);
+        }
+
         let vs = vs.borrow();
+        let input_channels = config.residual_block_channels.input_channels() as i64;
         let match_channel_conv = conv2d(
             vs,
-            16,
-            config.residual_block_channels as i64,
+            config.input_channels() as i64,
+            input_channels,
             3,
             ConvConfig {
                 padding: 1,
                 ..Default::default()
             },
         );
-        let match_channel_bn = batch_norm2d(
-            vs,
-            config.residual_block_channels as i64,
-            Default::default(),
-        );
+        let match_channel_norm = norm2d(vs, config.norm, input_channels);
         let mut residual_blocks = Vec::with_capacity(config.residual_blocks);
+        let mut pooled = Vec::with_capacity(config.residual_blocks);
+        let mut prev_channels = input_channels;
 
-        for _ in 0..config.residual_blocks {
-            residual_blocks.push(residual_block(vs, config.residual_block_channels as i64));
+        let (stage_starts, transition) = match &config.stages {
+            Some(stages) => (
+                stages.stage_starts(config.residual_blocks),
+                stages.transition,
+            ),
+            None => (vec![false; config.residual_blocks], Default::default()),
+        };
+
+        for (channels, stage_start) in config
+            .residual_block_channels
+            .block_channels(config.residual_blocks)
+            .into_iter()
+            .zip(stage_starts)
+        {
+            let channels = channels as i64;
+            let projection = if channels != prev_channels
+                || (stage_start && transition == StageTransition::Projection)
+            {
+                Some(conv2d(vs, prev_channels, channels, 1, Default::default()))
+            } else {
+                None
+            };
+
+            residual_blocks.push((projection, residual_block(vs, channels, config.norm)));
+            pooled.push(stage_start && transition == StageTransition::Pool);
+            prev_channels = channels;
         }
 
+        let output_size = config.output_size() as i64;
         let fc0 = linear(
             vs,
-            config.residual_block_channels as i64
-                * config.board_size as i64
-                * config.board_size as i64,
+            config.residual_block_channels.output_channels() as i64 * output_size * output_size,
             config.fc0_channels as i64,
             Default::default(),
         );
@@ -70,14 +347,27 @@ impl Model {
             Default::default(),
         );
 
+        let input_bn = config.normalize_input.then(|| {
+            batch_norm2d(
+                vs / "input_norm",
+                config.input_channels() as i64,
+                BatchNormConfig {
+                    affine: false,
+                    ..Default::default()
+                },
+            )
+        });
+
         Self {
             device: vs.device(),
             config,
             match_channel_conv,
-            match_channel_bn,
+            match_channel_norm,
             residual_blocks,
+            pooled,
             fc0,
             fc1,
+            input_bn,
         }
     }
 
@@ -104,98 +394,290 @@ impl Model {
             &from.match_channel_conv,
             weight,
         );
-        copy_weights_batch_norm2d(&mut self.match_channel_bn, &from.match_channel_bn, weight);
+        copy_weights_norm(
+            &mut self.match_channel_norm,
+            &from.match_channel_norm,
+            weight,
+        );
 
-        for (block_to, block_from) in self
+        for ((projection_to, block_to), (projection_from, block_from)) in self
             .residual_blocks
             .iter_mut()
             .zip(from.residual_blocks.iter())
         {
+            if let (Some(projection_to), Some(projection_from)) = (projection_to, projection_from) {
+                copy_weights_conv2d(projection_to, projection_from, weight);
+            }
+
             copy_weights_residual_block(block_to, block_from, weight);
         }
 
         copy_weights_linear(&mut self.fc0, &from.fc0, weight);
         copy_weights_linear(&mut self.fc1, &from.fc1, weight);
+
+        if let (Some(input_bn), Some(from_input_bn)) = (&mut self.input_bn, &from.input_bn) {
+            copy_weights_batch_norm2d(input_bn, from_input_bn, weight);
+        }
     }
-}
 
-impl ModuleT for Model {
-    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
-        let mut x = xs
-            .to_device(self.device)
-            .view([
-                -1,
-                16,
-                self.config.board_size as i64,
-                self.config.board_size as i64,
-            ])
+    /// Copies the batch norm running statistics of the given model as is, whatever weight its
+    /// parameters were blended with.
+    pub fn copy_bn_stats_from(&mut self, from: &Model) {
+        copy_stats_norm(&mut self.match_channel_norm, &from.match_channel_norm);
+
+        for ((_, block_to), (_, block_from)) in self
+            .residual_blocks
+            .iter_mut()
+            .zip(from.residual_blocks.iter())
+        {
+            copy_stats_residual_block(block_to, block_from);
+        }
+
+        if let (Some(input_bn), Some(from_input_bn)) = (&mut self.input_bn, &from.input_bn) {
+            copy_stats_batch_norm2d(input_bn, from_input_bn);
+        }
+    }
+
+    /// Runs the model in evaluation mode and returns the named intermediate activations.
+    ///
+    /// The first entry is the output of the channel-matching conv (`match_conv`), followed by the
+    /// output of each residual block (`residual_block_{i}`).
+    pub fn activations(&self, xs: &Tensor) -> Vec<(String, Tensor)> {
+        let mut activations = Vec::with_capacity(1 + self.residual_blocks.len());
+        let _ = self.forward_impl(xs, false, Some(&mut activations));
+        activations
+    }
+
+    fn forward_impl(
+        &self,
+        xs: &Tensor,
+        train: bool,
+        mut activations: Option<&mut Vec<(String, Tensor)>>,
+    ) -> Tensor {
+        let mut x = self
+            .normalize_input(xs, train)
             .apply(&self.match_channel_conv)
-            .apply_t(&self.match_channel_bn, train)
+            .apply_t(&self.match_channel_norm, train)
             .relu();
 
-        for block in self.residual_blocks.iter() {
+        if let Some(activations) = activations.as_deref_mut() {
+            activations.push(("match_conv".to_owned(), x.shallow_clone()));
+        }
+
+        for (index, (projection, block)) in self.residual_blocks.iter().enumerate() {
+            if self.pooled[index] {
+                x = x.max_pool2d([2, 2], [2, 2], [0, 0], [1, 1], true);
+            }
+
+            if let Some(projection) = projection {
+                x = x.apply(projection);
+            }
+
             x = x.apply_t(block, train);
+
+            if let Some(activations) = activations.as_deref_mut() {
+                activations.push((format!("residual_block_{}", index), x.shallow_clone()));
+            }
         }
 
         x.flatten(1, -1).apply(&self.fc0).relu().apply(&self.fc1)
     }
 }
 
-pub fn encode_batched_board(boards: &[&[(Turn, Board); 4]]) -> Tensor {
-    no_grad(|| {
-        let encoded = Tensor::zeros([boards.len() as i64, 16, 15, 15], tch::kind::FLOAT_CPU);
+impl Model {
+    /// Reshapes the input into `[batch, channels, size, size]` planes on the model's device, and
+    /// standardizes them with `normalize_input`.
+    fn normalize_input(&self, xs: &Tensor, train: bool) -> Tensor {
+        let xs = xs.to_device(self.device).view([
+            -1,
+            self.config.input_channels() as i64,
+            self.config.board_size as i64,
+            self.config.board_size as i64,
+        ]);
 
-        for (i, boards) in boards.iter().enumerate() {
-            let board_tensor = create_board_tensor(boards);
-            encoded
-                .slice(0, i as i64, (i + 1) as i64, 1)
-                .copy_(&board_tensor);
+        match &self.input_bn {
+            Some(input_bn) => xs.apply_t(input_bn, train),
+            None => xs,
         }
+    }
+}
+
+impl ModuleT for Model {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        self.forward_impl(xs, train, None)
+    }
+}
 
-        encoded
+pub fn encode_batched_board(boards: &[&[(Turn, Board); 4]], config: &ModelConfig) -> Tensor {
+    no_grad(|| {
+        let encoded = boards
+            .iter()
+            .map(|boards| create_board_tensor(boards, config))
+            .collect::<Vec<_>>();
+
+        Tensor::stack(&encoded, 0)
     })
 }
 
-fn create_board_tensor(boards: &[(Turn, Board); 4]) -> Tensor {
-    let encoded = Tensor::zeros([1, 16, 15, 15], tch::kind::FLOAT_CPU);
+/// Encodes every `(frames, action)` pair under each of the given symmetries.
+///
+/// Each position is encoded once and its cells are then permuted per symmetry, instead of
+/// transforming and encoding every board separately. The output holds `boards.len() *
+/// symmetries.len()` encodings, grouped by position and in the order of `symmetries` within a
+/// group, along with the actions mapped by the same symmetries.
+pub fn encode_batched_board_augmented(
+    boards: &[(&[(Turn, Board); 4], usize)],
+    symmetries: &[Symmetry],
+    config: &ModelConfig,
+) -> (Tensor, Vec<usize>) {
+    let board_size = config.board_size;
+    let area = board_size * board_size;
+
+    let actions = Vec::from_iter(boards.iter().flat_map(|&(_, action)| {
+        symmetries
+            .iter()
+            .map(move |symmetry| symmetry.map_index(action, board_size))
+    }));
+
+    let frames = Vec::from_iter(boards.iter().map(|&(frames, _)| frames));
+    let encoded = encode_batched_board(&frames, config).flatten(2, 3);
+
+    let augmented = no_grad(|| {
+        // the cell each transformed cell is read from
+        let sources = Vec::from_iter(symmetries.iter().flat_map(|symmetry| {
+            let inverse = symmetry.inverse();
+            (0..area).map(move |index| inverse.map_index(index, board_size) as i64)
+        }));
+        let sources = Tensor::from_slice(&sources);
+
+        encoded.index_select(2, &sources).view([
+            boards.len() as i64,
+            config.input_channels() as i64,
+            symmetries.len() as i64,
+            board_size as i64,
+            board_size as i64,
+        ])
+    })
+    .transpose(1, 2)
+    .reshape([
+        (boards.len() * symmetries.len()) as i64,
+        config.input_channels() as i64,
+        board_size as i64,
+        board_size as i64,
+    ]);
+
+    (augmented, actions)
+}
 
-    for (i, (turn, board)) in boards.iter().enumerate() {
-        let encoded = encoded.slice(1, i as i64 * 4, (i as i64 + 1) * 4, 1);
+/// Encodes a single `(turn, board)` frame into a tensor of shape `[4, size, size]`.
+///
+/// The planes are, in order:
+/// 0. the turn (`1` for black, `-1` for white)
+/// 1. empty cells
+/// 2. stones of `turn`
+/// 3. stones of the opponent
+///
+/// Without `include_turn_plane`, the turn plane is left out and the shape is `[3, size, size]`.
+pub fn encode_single_frame(turn: Turn, board: &Board, include_turn_plane: bool) -> Tensor {
+    let board_size = board.board_size();
+    let area = board_size * board_size;
+    let offset = if include_turn_plane { 1 } else { 0 };
+    let channels = offset + 3;
+    let mut data = vec![0f32; channels * area];
 
-        let point_of_view = (*turn).into();
+    let point_of_view = turn.into();
+
+    if include_turn_plane {
         let turn = match turn {
-            Turn::Black => 1f64,
-            Turn::White => -1f64,
+            Turn::Black => 1f32,
+            Turn::White => -1f32,
         };
-        let _ = encoded.slice(1, 0, 1, 1).fill_(turn);
-
-        let mut data = vec![0f32; 3 * 15 * 15];
-
-        for (i, cell) in board.cells().iter().enumerate() {
-            let offset = match cell {
-                Cell::Empty => 0,
-                &cell => {
-                    if cell == point_of_view {
-                        1
-                    } else {
-                        2
-                    }
-                }
-            };
-            data[(offset * 15 * 15) + i] = 1f32;
+        data[..area].fill(turn);
+    }
+
+    for (i, &cell) in board.cells().iter().enumerate() {
+        let plane = match cell {
+            Cell::Empty => 0,
+            cell if cell == point_of_view => 1,
+            _ => 2,
+        };
+        data[(offset + plane) * area + i] = 1f32;
+    }
+
+    Tensor::from_slice(&data).view([channels as i64, board_size as i64, board_size as i64])
+}
+
+/// Encodes the legal cells of the board into a boolean tensor of shape `[size * size]`.
+pub fn legal_mask_tensor(board: &Board) -> Tensor {
+    Tensor::from_slice(&board.legal_mask())
+}
+
+/// Encodes a frame with the planes of `encode_single_frame`, followed by the legal move plane
+/// (`1` for legal cells) with `include_legal_plane`.
+fn encode_frame(turn: Turn, board: &Board, config: &ModelConfig) -> Tensor {
+    let frame = encode_single_frame(turn, board, config.include_turn_plane);
+
+    if !config.include_legal_plane {
+        return frame;
+    }
+
+    let board_size = board.board_size() as i64;
+    let legal_plane = legal_mask_tensor(board)
+        .to_kind(Kind::Float)
+        .view([1, board_size, board_size]);
+
+    Tensor::cat(&[frame, legal_plane], 0)
+}
+
+fn create_board_tensor(boards: &[(Turn, Board); 4], config: &ModelConfig) -> Tensor {
+    match config.encoding {
+        EncodingMode::Stacked => {
+            let frames = boards
+                .iter()
+                .map(|(turn, board)| encode_frame(*turn, board, config))
+                .collect::<Vec<_>>();
+
+            Tensor::cat(&frames, 0)
         }
+        EncodingMode::Recency => {
+            let (turn, board) = &boards[3];
+            let frame = encode_frame(*turn, board, config);
 
-        encoded
-            .slice(1, 1, 4, 1)
-            .copy_(&Tensor::from_slice(&data).view([1, 3, 15, 15]));
+            Tensor::cat(&[frame, encode_recency_plane(boards)], 0)
+        }
     }
+}
+
+/// Encodes how recently each stone of the latest frame was placed into a `[1, size, size]` plane.
+///
+/// A stone's age is the number of earlier frames that already contain it, and its value is
+/// `0.5^age`. Empty cells are `0`.
+fn encode_recency_plane(boards: &[(Turn, Board); 4]) -> Tensor {
+    let (_, latest) = &boards[3];
+    let board_size = latest.board_size();
+    let mut data = vec![0f32; board_size * board_size];
 
-    encoded
+    for (i, &cell) in latest.cells().iter().enumerate() {
+        if cell.is_empty() {
+            continue;
+        }
+
+        let age = boards[..3]
+            .iter()
+            .rev()
+            .take_while(|(_, board)| board.cells()[i] == cell)
+            .count();
+        data[i] = 0.5f32.powi(age as i32);
+    }
+
+    Tensor::from_slice(&data).view([1, board_size as i64, board_size as i64])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::replay::generate_history_boards;
+    use figment::providers::{Format, Toml};
     use gomoku_core::game::Game;
     use tch::nn::VarStore;
 
@@ -217,10 +699,203 @@ mod tests {
         boards.push((result.turn_was, result.board_was));
 
         let boards = boards.try_into().unwrap();
-        let encoded = encode_batched_board(&[&boards]);
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        let encoded = encode_batched_board(&[&boards], &config);
+        assert_eq!(encoded.size(), &[1, 16, 15, 15]);
         encoded.print();
     }
 
+    #[test]
+    fn test_encode_batched_board_augmented() {
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: true,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+
+        let mut game = Game::new(15, 5);
+        let mut positions = vec![];
+        for (index, action) in [(0, 17), (1, 33), (45, 112), (46, 200)] {
+            game.place_stone(index).unwrap();
+            positions.push((generate_history_boards(game.turn(), &game), action));
+        }
+
+        let boards = Vec::from_iter(positions.iter().map(|(boards, action)| (boards, *action)));
+        let (encoded, actions) = encode_batched_board_augmented(&boards, &Symmetry::ALL, &config);
+        assert_eq!(encoded.size(), &[32, 20, 15, 15]);
+
+        // transform and encode every board one at a time
+        let mut naive_boards = vec![];
+        let mut naive_actions = vec![];
+        for (boards, action) in &positions {
+            for symmetry in Symmetry::ALL {
+                naive_boards.push(
+                    boards
+                        .clone()
+                        .map(|(turn, board)| (turn, board.apply_symmetry(symmetry))),
+                );
+                naive_actions.push(symmetry.map_index(*action, 15));
+            }
+        }
+        let naive = encode_batched_board(&Vec::from_iter(naive_boards.iter()), &config);
+
+        assert_eq!(actions, naive_actions);
+        assert!(encoded.equal(&naive));
+    }
+
+    #[test]
+    fn test_encode_recency() {
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Recency,
+        };
+        assert_eq!(config.input_channels(), 5);
+
+        let mut game = Game::new(15, 5);
+        for index in [112, 113, 127, 128, 142, 143, 157] {
+            game.place_stone(index).unwrap();
+        }
+
+        let boards = generate_history_boards(game.turn(), &game);
+        let encoded = encode_batched_board(&[&boards], &config);
+        assert_eq!(encoded.size(), &[1, 5, 15, 15]);
+
+        let recency: Vec<f32> = encoded.get(0).get(4).view([-1]).try_into().unwrap();
+
+        // white to move: the latest stone and white's own last stone appeared in the latest frame
+        assert_eq!(recency[157], 1.0);
+        assert_eq!(recency[143], 1.0);
+        assert_eq!(recency[142], 0.5);
+        assert_eq!(recency[128], 0.5);
+        assert_eq!(recency[127], 0.25);
+        assert_eq!(recency[113], 0.25);
+        assert_eq!(recency[112], 0.125);
+        assert_eq!(recency[0], 0.0);
+        assert!(recency.iter().all(|&value| value <= recency[157]));
+
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config);
+        assert_eq!(model.forward_t(&encoded, false).size(), &[1, 15 * 15]);
+    }
+
+    #[test]
+    fn test_model_config_toml() {
+        let configs = [
+            ModelConfig {
+                board_size: 15,
+                residual_blocks: 2,
+                residual_block_channels: 8.into(),
+                stages: None,
+                fc0_channels: 8,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
+            },
+            ModelConfig {
+                board_size: 9,
+                residual_blocks: 2,
+                residual_block_channels: vec![16, 8].into(),
+                stages: None,
+                fc0_channels: 4,
+                include_turn_plane: false,
+                include_legal_plane: true,
+                normalize_input: false,
+                norm: NormKind::GroupNorm { groups: 4 },
+                encoding: EncodingMode::Recency,
+            },
+        ];
+
+        for config in configs {
+            let toml = toml::to_string(&config).unwrap();
+            assert_eq!(toml::from_str::<ModelConfig>(&toml).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn test_encode_legal_plane() {
+        let mut config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        let game = Game::from_moves(15, 5, &[112, 113, 0]).unwrap();
+        let boards = generate_history_boards(game.turn(), &game);
+
+        let without = encode_batched_board(&[&boards], &config);
+        config.include_legal_plane = true;
+        let with = encode_batched_board(&[&boards], &config);
+
+        assert_eq!(config.frame_channels(), 5);
+        assert_eq!(without.size(), &[1, 16, 15, 15]);
+        assert_eq!(with.size(), &[1, 20, 15, 15]);
+
+        // the last plane of the latest frame marks the empty cells
+        let planes: Vec<Vec<f32>> = with.view([20, -1]).try_into().unwrap();
+        for (i, &cell) in game.board().cells().iter().enumerate() {
+            assert_eq!(planes[19][i] == 1f32, cell.is_empty());
+        }
+
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config);
+        assert_eq!(model.forward_t(&with, false).size(), &[1, 15 * 15]);
+    }
+
+    #[test]
+    fn test_encode_single_frame() {
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        board.set_cell(16, Cell::White);
+        board.set_cell(224, Cell::Black);
+
+        let frame = encode_single_frame(Turn::White, &board, true);
+        assert_eq!(frame.size(), &[4, 15, 15]);
+
+        let planes: Vec<Vec<f32>> = frame.view([4, -1]).try_into().unwrap();
+
+        assert!(planes[0].iter().all(|&value| value == -1f32));
+
+        for (i, &cell) in board.cells().iter().enumerate() {
+            assert_eq!(planes[1][i] == 1f32, cell == Cell::Empty);
+            assert_eq!(planes[2][i] == 1f32, cell == Cell::White);
+            assert_eq!(planes[3][i] == 1f32, cell == Cell::Black);
+        }
+    }
+
     #[test]
     fn test_model_cpu() {
         let vs = VarStore::new(tch::Device::Cpu);
@@ -229,8 +904,14 @@ mod tests {
             ModelConfig {
                 board_size: 15,
                 residual_blocks: 2,
-                residual_block_channels: 32,
+                residual_block_channels: 32.into(),
+                stages: None,
                 fc0_channels: 32,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
             },
         );
 
@@ -243,6 +924,412 @@ mod tests {
         q.to_device(tch::Device::Cpu).print();
     }
 
+    #[test]
+    fn test_model_norm_kinds() {
+        for norm in [
+            NormKind::BatchNorm,
+            NormKind::GroupNorm { groups: 4 },
+            NormKind::None,
+        ] {
+            let config = ModelConfig {
+                board_size: 15,
+                residual_blocks: 2,
+                residual_block_channels: 8.into(),
+                stages: None,
+                fc0_channels: 8,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm,
+                encoding: EncodingMode::Stacked,
+            };
+            let online_vs = VarStore::new(tch::Device::Cpu);
+            let online = Model::new(online_vs.root(), config.clone());
+            let target_vs = VarStore::new(tch::Device::Cpu);
+            let mut target = Model::new(target_vs.root(), config);
+            target.copy_weights_from(&online, None);
+            target.copy_bn_stats_from(&online);
+
+            let xs = Tensor::randn([4, 16, 15, 15], tch::kind::FLOAT_CPU);
+            for train in [true, false] {
+                let q = online.forward_t(&xs, train);
+                assert_eq!(q.size(), &[4, 15 * 15], "{:?}", norm);
+                assert!(bool::try_from(q.isfinite().all()).unwrap(), "{:?}", norm);
+            }
+
+            // a full copy makes the target compute the same values
+            let online_q = online.forward_t(&xs, false);
+            let target_q = target.forward_t(&xs, false);
+            assert!(
+                online_q.allclose(&target_q, 1e-5, 1e-6, false),
+                "{:?}",
+                norm
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "3 groups don't divide 8 channels")]
+    fn test_group_norm_indivisible_channels() {
+        let vs = VarStore::new(tch::Device::Cpu);
+        Model::new(
+            vs.root(),
+            ModelConfig {
+                board_size: 15,
+                residual_blocks: 1,
+                residual_block_channels: 8.into(),
+                stages: None,
+                fc0_channels: 8,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::GroupNorm { groups: 3 },
+                encoding: EncodingMode::Stacked,
+            },
+        );
+    }
+
+    #[test]
+    fn test_copy_bn_stats_from() {
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        let online_vs = VarStore::new(tch::Device::Cpu);
+        let online = Model::new(online_vs.root(), config.clone());
+        let target_vs = VarStore::new(tch::Device::Cpu);
+        let mut target = Model::new(target_vs.root(), config);
+        target.copy_weights_from(&online, None);
+
+        // a training forward pass moves the online running statistics
+        let xs = Tensor::randn([4, 16, 15, 15], tch::kind::FLOAT_CPU) + 3.0;
+        let _ = online.forward_t(&xs, true);
+
+        fn bn(norm: &Norm) -> &BatchNorm {
+            norm.batch_norm().unwrap()
+        }
+
+        target.copy_weights_from(&online, Some(0.001));
+        assert!(!bn(&target.match_channel_norm)
+            .running_mean
+            .equal(&bn(&online.match_channel_norm).running_mean));
+
+        target.copy_bn_stats_from(&online);
+        assert!(bn(&target.match_channel_norm)
+            .running_mean
+            .equal(&bn(&online.match_channel_norm).running_mean));
+
+        let (_, target_block) = &target.residual_blocks[0];
+        let (_, online_block) = &online.residual_blocks[0];
+        assert!(bn(&target_block.norm1)
+            .running_mean
+            .equal(&bn(&online_block.norm1).running_mean));
+        assert!(bn(&target_block.norm2)
+            .running_var
+            .equal(&bn(&online_block.norm2).running_var));
+    }
+
+    #[test]
+    fn test_normalize_input() {
+        let mut config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: true,
+            normalize_input: true,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Recency,
+        };
+
+        // positions from a game, with either player to move
+        let mut game = Game::new(15, 5);
+        let mut positions = vec![];
+        for index in [112, 113, 97, 98, 127, 83, 142, 157, 82, 67, 96, 128] {
+            game.place_stone(index).unwrap();
+            positions.push(generate_history_boards(game.turn(), &game));
+        }
+        let encoded = encode_batched_board(&Vec::from_iter(positions.iter()), &config);
+
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config.clone());
+        let normalized = model.normalize_input(&encoded, true);
+        let means: Vec<f32> = normalized
+            .mean_dim([0, 2, 3].as_slice(), false, Kind::Float)
+            .try_into()
+            .unwrap();
+        assert_eq!(means.len(), config.input_channels());
+        assert!(means.iter().all(|mean| mean.abs() < 1e-4), "{:?}", means);
+        assert_eq!(model.forward_t(&encoded, false).size(), &[12, 15 * 15]);
+
+        // without normalization, the planes are left as is
+        config.normalize_input = false;
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config);
+        assert!(model.normalize_input(&encoded, true).equal(&encoded));
+    }
+
+    #[test]
+    fn test_model_stages() {
+        let mut config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 4,
+            residual_block_channels: vec![8, 8, 16, 16].into(),
+            stages: Some(Stages {
+                sizes: vec![2, 2],
+                transition: StageTransition::Pool,
+            }),
+            fc0_channels: 16,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        assert_eq!(config.output_size(), 8);
+
+        let batch = 4;
+        let xs = Tensor::randn([batch, 16 * 15 * 15], tch::kind::FLOAT_CPU);
+
+        let online_vs = VarStore::new(tch::Device::Cpu);
+        let online = Model::new(online_vs.root(), config.clone());
+        assert_eq!(online.forward_t(&xs, true).size(), &[batch, 15 * 15]);
+
+        let target_vs = VarStore::new(tch::Device::Cpu);
+        let mut target = Model::new(target_vs.root(), config.clone());
+        target.copy_weights_from(&online, None);
+        assert!(target
+            .forward_t(&xs, false)
+            .equal(&online.forward_t(&xs, false)));
+
+        // a projection between stages keeps the planes' size
+        config.stages = Some(Stages {
+            sizes: vec![1, 3],
+            transition: StageTransition::Projection,
+        });
+        config.residual_block_channels = 8.into();
+        assert_eq!(config.output_size(), 15);
+
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config);
+        assert!(model.residual_blocks[1].0.is_some());
+        assert!(model.residual_blocks[2].0.is_none());
+        assert_eq!(model.forward_t(&xs, false).size(), &[batch, 15 * 15]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 3,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        let with_stages = |sizes: Vec<usize>| ModelConfig {
+            stages: Some(Stages {
+                sizes,
+                transition: StageTransition::Pool,
+            }),
+            ..config.clone()
+        };
+
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(with_stages(vec![2, 1]).validate(), Ok(()));
+        assert_eq!(
+            with_stages(vec![]).validate(),
+            Err(ModelConfigError::NoStages)
+        );
+        assert_eq!(
+            with_stages(vec![3, 0]).validate(),
+            Err(ModelConfigError::EmptyStage { stage: 1 })
+        );
+        assert_eq!(
+            with_stages(vec![2, 2]).validate(),
+            Err(ModelConfigError::StageSizeMismatch {
+                blocks: 4,
+                residual_blocks: 3,
+            })
+        );
+        assert_eq!(
+            ModelConfig {
+                residual_block_channels: vec![8, 8].into(),
+                ..config.clone()
+            }
+            .validate(),
+            Err(ModelConfigError::BlockChannelsMismatch {
+                widths: 2,
+                residual_blocks: 3,
+            })
+        );
+        assert_eq!(
+            ModelConfig {
+                residual_block_channels: vec![8, 8, 12].into(),
+                norm: NormKind::GroupNorm { groups: 4 },
+                ..config.clone()
+            }
+            .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            ModelConfig {
+                residual_block_channels: vec![8, 8, 6].into(),
+                norm: NormKind::GroupNorm { groups: 4 },
+                ..config.clone()
+            }
+            .validate(),
+            Err(ModelConfigError::IndivisibleGroups {
+                groups: 4,
+                channels: 6,
+            })
+        );
+        assert_eq!(
+            ModelConfig {
+                norm: NormKind::GroupNorm { groups: 0 },
+                ..config.clone()
+            }
+            .validate(),
+            Err(ModelConfigError::IndivisibleGroups {
+                groups: 0,
+                channels: 8,
+            })
+        );
+
+        let toml = toml::to_string(&with_stages(vec![])).unwrap();
+        assert!(matches!(
+            ModelConfig::from_figment(&Figment::new().merge(Toml::string(&toml))),
+            Err(AgentError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_stage_starts() {
+        let stages = Stages {
+            sizes: vec![2, 1, 3],
+            transition: StageTransition::Pool,
+        };
+
+        assert_eq!(
+            stages.stage_starts(6),
+            vec![false, false, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_model_tapered() {
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(
+            vs.root(),
+            ModelConfig {
+                board_size: 15,
+                residual_blocks: 3,
+                residual_block_channels: vec![32, 16, 8].into(),
+                stages: None,
+                fc0_channels: 16,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
+            },
+        );
+
+        let batch = 4;
+        let xs = Tensor::randn([batch, 16 * 15 * 15], tch::kind::FLOAT_CPU);
+        let q = model.forward_t(&xs, false);
+
+        assert_eq!(q.size(), &[batch, 15 * 15]);
+    }
+
+    #[test]
+    fn test_model_without_turn_plane() {
+        let config = ModelConfig {
+            board_size: 15,
+            residual_blocks: 1,
+            residual_block_channels: 8.into(),
+            stages: None,
+            fc0_channels: 8,
+            include_turn_plane: false,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        };
+        assert_eq!(config.frame_channels(), 3);
+        assert_eq!(config.input_channels(), 12);
+
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(vs.root(), config.clone());
+
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+        let frame = encode_single_frame(Turn::White, game.board(), false);
+        assert_eq!(frame.size(), &[3, 15, 15]);
+
+        let boards = [
+            (Turn::White, Board::new(15)),
+            (Turn::White, Board::new(15)),
+            (Turn::White, Board::new(15)),
+            (Turn::White, game.board().clone()),
+        ];
+        let encoded = encode_batched_board(&[&boards, &boards], &config);
+        assert_eq!(encoded.size(), &[2, 12, 15, 15]);
+
+        let q = model.forward_t(&encoded, false);
+        assert_eq!(q.size(), &[2, 15 * 15]);
+    }
+
+    #[test]
+    fn test_model_activations() {
+        let vs = VarStore::new(tch::Device::Cpu);
+        let model = Model::new(
+            vs.root(),
+            ModelConfig {
+                board_size: 5,
+                residual_blocks: 2,
+                residual_block_channels: vec![4, 2].into(),
+                stages: None,
+                fc0_channels: 4,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
+            },
+        );
+
+        let batch = 3;
+        let xs = Tensor::randn([batch, 16 * 5 * 5], tch::kind::FLOAT_CPU);
+        let activations = model.activations(&xs);
+
+        let names = activations
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["match_conv", "residual_block_0", "residual_block_1"]
+        );
+        assert_eq!(activations[0].1.size(), &[batch, 4, 5, 5]);
+        assert_eq!(activations[1].1.size(), &[batch, 4, 5, 5]);
+        assert_eq!(activations[2].1.size(), &[batch, 2, 5, 5]);
+    }
+
     #[cfg(target_os = "macos")]
     #[test]
     fn test_model_mps() {
@@ -252,8 +1339,14 @@ mod tests {
             ModelConfig {
                 board_size: 15,
                 residual_blocks: 2,
-                residual_block_channels: 32,
+                residual_block_channels: 32.into(),
+                stages: None,
                 fc0_channels: 32,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
             },
         );
 
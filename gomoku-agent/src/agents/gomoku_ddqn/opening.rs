@@ -0,0 +1,109 @@
+use gomoku_core::board::Board;
+use std::fmt::Display;
+
+/// The moves an agent prefers in a few common opening positions; see
+/// `GomokuDDQNAgent::opening_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningReport {
+    pub board_size: usize,
+    pub entries: Vec<OpeningEntry>,
+}
+
+/// The preferred moves in one opening position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningEntry {
+    /// The moves leading to the position from the empty board.
+    pub moves: Vec<usize>,
+    /// The best moves and their Q-values, best first.
+    pub top_moves: Vec<(usize, f32)>,
+}
+
+/// Returns the move sequences of the report's positions: the empty board, a stone in the center,
+/// and the center answered diagonally and directly. Answers that don't fit on a tiny board are
+/// left out.
+pub(super) fn opening_positions(board_size: usize) -> Vec<Vec<usize>> {
+    let center = Board::new(board_size).center_index();
+    let answers = [
+        center.checked_sub(board_size + 1),
+        center.checked_sub(board_size),
+    ];
+
+    let mut positions = vec![vec![], vec![center]];
+    positions.extend(
+        answers
+            .into_iter()
+            .flatten()
+            .map(|answer| vec![center, answer]),
+    );
+    positions
+}
+
+impl Display for OpeningReport {
+    /// Writes one line per position, e.g. `H8 G7 -> I9 (0.512), G9 (0.498)`, with the empty board
+    /// written as `(empty)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let board = Board::new(self.board_size);
+        let position = |index: usize| {
+            board
+                .index_to_position(index)
+                .unwrap_or_else(|| index.to_string())
+        };
+
+        for entry in &self.entries {
+            let moves = if entry.moves.is_empty() {
+                "(empty)".to_owned()
+            } else {
+                Vec::from_iter(entry.moves.iter().map(|&index| position(index))).join(" ")
+            };
+            let top_moves = Vec::from_iter(
+                entry
+                    .top_moves
+                    .iter()
+                    .map(|&(index, value)| format!("{} ({:.3})", position(index), value)),
+            );
+
+            writeln!(f, "{} -> {}", moves, top_moves.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gomoku_core::game::Game;
+
+    #[test]
+    fn test_opening_positions() {
+        for moves in opening_positions(15) {
+            Game::from_moves(15, 5, &moves).unwrap();
+        }
+
+        assert_eq!(opening_positions(15)[1], vec![112]);
+        assert_eq!(opening_positions(15).len(), 4);
+        assert_eq!(opening_positions(1), vec![vec![], vec![0]]);
+    }
+
+    #[test]
+    fn test_display() {
+        let report = OpeningReport {
+            board_size: 15,
+            entries: vec![
+                OpeningEntry {
+                    moves: vec![],
+                    top_moves: vec![(112, 0.5), (0, -0.25)],
+                },
+                OpeningEntry {
+                    moves: vec![112, 96],
+                    top_moves: vec![(128, 1.0)],
+                },
+            ],
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "(empty) -> H8 (0.500), A1 (-0.250)\nH8 G7 -> I9 (1.000)\n"
+        );
+    }
+}
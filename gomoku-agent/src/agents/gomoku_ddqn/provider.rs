@@ -1,19 +1,35 @@
-use super::{agent::GomokuDDQNAgent, model::ModelConfig};
-use crate::{agent::Agent, agent_provider::AgentProvider};
+use super::{
+    agent::GomokuDDQNAgent,
+    model::{EncodingMode, ModelConfig},
+};
+use crate::{agent::Agent, agent_provider::AgentProvider, nn_utils::NormKind};
 
 pub struct GomokuDDQNProvider;
 
+impl GomokuDDQNProvider {
+    /// Creates the agent as its concrete type, giving access to DDQN-specific methods.
+    pub fn create_ddqn_agent(&self) -> GomokuDDQNAgent {
+        GomokuDDQNAgent::new(ModelConfig {
+            board_size: 15,
+            residual_blocks: 10,
+            residual_block_channels: 128.into(),
+            stages: None,
+            fc0_channels: 128,
+            include_turn_plane: true,
+            include_legal_plane: false,
+            normalize_input: false,
+            norm: NormKind::BatchNorm,
+            encoding: EncodingMode::Stacked,
+        })
+    }
+}
+
 impl AgentProvider for GomokuDDQNProvider {
     fn name(&self) -> String {
         "gomoku-ddqn".to_owned()
     }
 
     fn create_agent(&self) -> Box<dyn Agent> {
-        Box::new(GomokuDDQNAgent::new(ModelConfig {
-            board_size: 15,
-            residual_blocks: 10,
-            residual_block_channels: 128,
-            fc0_channels: 128,
-        }))
+        Box::new(self.create_ddqn_agent())
     }
 }
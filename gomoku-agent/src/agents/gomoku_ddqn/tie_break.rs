@@ -0,0 +1,124 @@
+use rand::{seq::SliceRandom, Rng};
+
+/// How `next_move` picks among moves sharing the highest Q-value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    /// The lowest index, like a plain argmax.
+    #[default]
+    First,
+    /// A random one, from a generator seeded with the given value.
+    Random(u64),
+    /// The one closest to the center of the board, then the lowest index.
+    Centermost,
+}
+
+/// Picks the index of the highest value, breaking ties with `tie_break`. `values` holds one value
+/// per cell of a `board_size` board, with illegal moves set to negative infinity.
+///
+/// Returns `None` if every value is negative infinity or NaN.
+pub fn select_move(
+    values: &[f32],
+    board_size: usize,
+    tie_break: TieBreak,
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    let best = values
+        .iter()
+        .copied()
+        .filter(|value| !value.is_nan())
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    if best == f32::NEG_INFINITY {
+        return None;
+    }
+
+    let tied = values
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &value)| (value == best).then_some(index))
+        .collect::<Vec<_>>();
+
+    match tie_break {
+        TieBreak::First => tied.first().copied(),
+        TieBreak::Random(_) => tied.choose(rng).copied(),
+        TieBreak::Centermost => {
+            // doubled coordinates keep the center integral on even-sized boards
+            let center = board_size as isize - 1;
+            tied.into_iter().min_by_key(|&index| {
+                let row = 2 * (index / board_size) as isize - center;
+                let column = 2 * (index % board_size) as isize - center;
+                row * row + column * column
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn tied_values() -> Vec<f32> {
+        // a 5x5 board with the top value at (0, 0), (0, 4) and (1, 1), and an illegal center
+        let mut values = vec![0f32; 25];
+        values[0] = 1.0;
+        values[4] = 1.0;
+        values[6] = 1.0;
+        values[12] = f32::NEG_INFINITY;
+        values
+    }
+
+    #[test]
+    fn test_first() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            select_move(&tied_values(), 5, TieBreak::First, &mut rng),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_centermost() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            select_move(&tied_values(), 5, TieBreak::Centermost, &mut rng),
+            Some(6)
+        );
+
+        // (1, 1) and (2, 2) are equally close to the center of a 4x4 board
+        let mut values = vec![0f32; 16];
+        values[5] = 1.0;
+        values[10] = 1.0;
+        assert_eq!(
+            select_move(&values, 4, TieBreak::Centermost, &mut rng),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_random() {
+        let values = tied_values();
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            Vec::from_iter(
+                (0..20).map(|_| select_move(&values, 5, TieBreak::Random(seed), &mut rng).unwrap()),
+            )
+        };
+
+        assert!(picks(7).iter().all(|index| [0, 4, 6].contains(index)));
+        assert_eq!(picks(7), picks(7));
+        assert!([0, 4, 6].iter().all(|index| picks(7).contains(index)));
+    }
+
+    #[test]
+    fn test_no_legal_move() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            select_move(&[f32::NEG_INFINITY; 4], 2, TieBreak::First, &mut rng),
+            None
+        );
+    }
+}
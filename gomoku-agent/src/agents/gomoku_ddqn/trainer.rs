@@ -1,21 +1,47 @@
-use super::{agent::GomokuDDQNAgent, model::Model};
+use super::{
+    agent::GomokuDDQNAgent,
+    logger::{
+        EpochReport, PlotLogger, RecentGameReport, StepReport, TensorBoardLogger, TrainLogger,
+    },
+    model::Model,
+};
 use crate::{
-    agent::Agent,
-    replay::{sample_replay, Opponent},
+    agent::{Agent, AgentError},
+    probe::ProbeSet,
+    replay::{
+        count_unique_positions, generate_demonstrations, EvictionPolicy, Exploration, Opponent,
+        ReplayBuffer, ReplayConfig, ReplayStream, RewardConfig,
+    },
 };
 use figment::Figment;
-use gomoku_core::game::{Game, Turn};
-use rand::{seq::IteratorRandom, Rng};
-use serde::Deserialize;
-use std::{collections::VecDeque, error::Error};
-use tch::nn::{Adam, OptimizerConfig};
+use gomoku_core::{
+    board::format_move,
+    game::{GameResult, Turn},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tch::{
+    nn::{Adam, AdamW, Optimizer, OptimizerConfig, RmsProp, Sgd, VarStore},
+    TchError,
+};
 
-pub struct GomokuDDQNTrainer;
+/// Trains a `GomokuDDQNAgent`, reporting to its loggers along with the TensorBoard and plot
+/// loggers enabled by the train options.
+pub struct GomokuDDQNTrainer {
+    loggers: Vec<Box<dyn TrainLogger>>,
+}
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TrainOptions {
     save_path: Option<String>,
     replay_buffer_size: usize,
+    /// How the replay buffer makes room once full. `lowest_priority` keeps the steps with the
+    /// largest rewards in magnitude.
+    eviction: EvictionPolicy,
     batch_size: usize,
     iterations: usize,
     training_steps: usize,
@@ -23,9 +49,51 @@ pub struct TrainOptions {
     epsilon_decay: f64,
     epsilon_min: f64,
     gamma: f64,
+    reward: RewardConfig,
+    td_target: TdTargetKind,
     learning_rate: f64,
+    optimizer: OptimizerKind,
+    /// Weight decay applied by the optimizer. Adam decays the weights directly (AdamW), while SGD
+    /// and RMSProp add it as an L2 penalty to the gradients.
+    weight_decay: f64,
     max_grad_norm: f64,
     tau: f64,
+    /// Whether the target network copies the batch norm running statistics of the online network
+    /// as is, instead of blending them with `tau` like the weights.
+    sync_target_bn_stats: bool,
+    /// Number of tactical self-play games recorded into the replay buffer before training.
+    demonstration_games: usize,
+    /// Sampling weight of demonstration steps relative to the agent's own steps.
+    demonstration_weight: f64,
+    /// How many times as likely steps ending a game are to be sampled as the others.
+    terminal_oversample: f64,
+    /// Whether random exploration moves are restricted to cells next to existing stones.
+    focused_exploration: bool,
+    /// Whether the agent only considers cells next to existing stones (the center on an empty
+    /// board) while training, both for its greedy moves and for random exploration.
+    restrict_to_candidates: bool,
+    /// Whether the agent choosing an occupied cell is recorded as a transition rewarded with
+    /// `reward.illegal_move`, followed by a random legal move, instead of panicking.
+    penalize_illegal: bool,
+    /// Whether to play the agent's weaker color more often, based on its recent win-rates.
+    adaptive_color: bool,
+    /// Opponent stages to train against, in order. Defaults to a single random-opponent stage.
+    curriculum: Vec<CurriculumStage>,
+    /// Number of games played against the current opponent after each epoch.
+    eval_games: usize,
+    /// Directory to write TensorBoard event files with the per-epoch loss, win-rate, epsilon and
+    /// mean policy entropy to.
+    tb_logdir: Option<String>,
+    /// Path of a PNG plot of the per-epoch loss and win-rate curves, redrawn after every epoch.
+    plot_path: Option<String>,
+    /// Path of a probe set, whose accuracy is reported after each epoch; see `ProbeSet`.
+    probe_path: Option<String>,
+    /// Whether black and white are played by separate networks, each with its own optimizer.
+    separate_color_nets: bool,
+    /// How many epochs apart a recent game against the current opponent is printed. `None` never
+    /// prints one, and is written as `0` since TOML has no null.
+    #[serde(with = "zero_as_none")]
+    recent_game_every: Option<usize>,
 }
 
 impl Default for TrainOptions {
@@ -33,6 +101,7 @@ impl Default for TrainOptions {
         Self {
             save_path: None,
             replay_buffer_size: 10000,
+            eviction: EvictionPolicy::default(),
             batch_size: 32,
             iterations: 100,
             training_steps: 10,
@@ -40,66 +109,313 @@ impl Default for TrainOptions {
             epsilon_decay: 0.99,
             epsilon_min: 0.01,
             gamma: 0.9,
+            reward: RewardConfig::default(),
+            td_target: TdTargetKind::default(),
             learning_rate: 0.0001,
+            optimizer: OptimizerKind::default(),
+            weight_decay: 0.0,
             max_grad_norm: 1.0,
             tau: 0.001,
+            sync_target_bn_stats: false,
+            demonstration_games: 0,
+            demonstration_weight: 1.0,
+            terminal_oversample: 1.0,
+            focused_exploration: false,
+            restrict_to_candidates: false,
+            penalize_illegal: false,
+            adaptive_color: false,
+            curriculum: vec![CurriculumStage {
+                opponent: Opponent::Random,
+                win_rate_threshold: 1.0,
+            }],
+            eval_games: 10,
+            tb_logdir: None,
+            plot_path: None,
+            probe_path: None,
+            separate_color_nets: false,
+            recent_game_every: Some(10),
+        }
+    }
+}
+
+impl TrainOptions {
+    /// Checks the values that deserialize fine but can't be trained with.
+    fn validate(&self) -> Result<(), AgentError> {
+        if self.curriculum.is_empty() {
+            return Err(figment::Error::from("curriculum must have at least one stage").into());
+        }
+
+        for (name, value) in [
+            ("demonstration_weight", self.demonstration_weight),
+            ("terminal_oversample", self.terminal_oversample),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(figment::Error::from(format!(
+                    "{} must be finite and non-negative, got {}",
+                    name, value
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the options, defaults and overrides included, as `train_options.toml` next to
+    /// `save_path`, and returns the written path.
+    fn save_resolved(&self, save_path: &str) -> io::Result<PathBuf> {
+        let path = Path::new(save_path).with_file_name("train_options.toml");
+        let toml = toml::to_string(self).map_err(io::Error::other)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, toml)?;
+        Ok(path)
+    }
+
+    /// Whether a recent game is printed after the given (zero-based) epoch.
+    fn shows_recent_game(&self, epoch: usize) -> bool {
+        match self.recent_game_every {
+            Some(every) if every != 0 => epoch.is_multiple_of(every),
+            _ => false,
+        }
+    }
+}
+
+/// (De)serializes an optional count with `0` standing for `None`.
+mod zero_as_none {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.unwrap_or(0) as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<usize>, D::Error> {
+        Ok(Option::<usize>::deserialize(deserializer)?.filter(|&value| value != 0))
+    }
+}
+
+/// A stage of the opponent curriculum.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurriculumStage {
+    opponent: Opponent,
+    /// The stage is left for the next one once the agent's evaluation win-rate against the
+    /// opponent exceeds this value. Ignored for the last stage.
+    win_rate_threshold: f64,
+}
+
+/// How the value of the next state is bootstrapped in the TD target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TdTargetKind {
+    /// The target network's value of the agent's greedy action (double DQN).
+    #[default]
+    QLearning,
+    /// The expected value of the target network under the agent's epsilon-greedy policy.
+    ExpectedSarsa,
+}
+
+/// The optimizer used to train the agent, along with its hyperparameters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OptimizerKind {
+    Adam { beta1: f64, beta2: f64 },
+    Sgd { momentum: f64, nesterov: bool },
+    RmsProp { alpha: f64, momentum: f64 },
+}
+
+impl Default for OptimizerKind {
+    fn default() -> Self {
+        let adam = Adam::default();
+        OptimizerKind::Adam {
+            beta1: adam.beta1,
+            beta2: adam.beta2,
+        }
+    }
+}
+
+impl OptimizerKind {
+    pub fn build(
+        self,
+        var_store: &VarStore,
+        learning_rate: f64,
+        weight_decay: f64,
+    ) -> Result<Optimizer, TchError> {
+        match self {
+            OptimizerKind::Adam { beta1, beta2 } => AdamW {
+                beta1,
+                beta2,
+                wd: weight_decay,
+                ..Default::default()
+            }
+            .build(var_store, learning_rate),
+            OptimizerKind::Sgd { momentum, nesterov } => Sgd {
+                momentum,
+                nesterov,
+                wd: weight_decay,
+                ..Default::default()
+            }
+            .build(var_store, learning_rate),
+            OptimizerKind::RmsProp { alpha, momentum } => RmsProp {
+                alpha,
+                momentum,
+                wd: weight_decay,
+                ..Default::default()
+            }
+            .build(var_store, learning_rate),
         }
     }
 }
 
 impl GomokuDDQNTrainer {
+    pub fn new(loggers: Vec<Box<dyn TrainLogger>>) -> Self {
+        Self { loggers }
+    }
+
+    pub fn into_loggers(self) -> Vec<Box<dyn TrainLogger>> {
+        self.loggers
+    }
+
     pub fn train(
         &mut self,
         agent: &mut GomokuDDQNAgent,
         epoches: usize,
         options: Figment,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let train_options: TrainOptions = options.extract().unwrap_or_default();
+    ) -> Result<(), AgentError> {
+        let train_options: TrainOptions = options.extract()?;
+        train_options.validate()?;
 
-        let mut target = Model::new(
-            agent.var_store().root().sub("train-target"),
-            agent.model().config().clone(),
-        );
-        target.copy_weights_from(agent.model(), None);
+        if let Some(save_path) = &train_options.save_path {
+            train_options.save_resolved(save_path)?;
+        }
 
-        let mut optimizer =
-            Adam::default().build(agent.var_store(), train_options.learning_rate)?;
+        let probe_set = match &train_options.probe_path {
+            Some(probe_path) => Some(ProbeSet::load(probe_path)?),
+            None => None,
+        };
 
-        let mut rng = rand::thread_rng();
-        let mut game = Game::new(15, 5);
-        let mut agent_turn = if rng.gen_bool(0.5) {
-            Turn::Black
+        if train_options.separate_color_nets {
+            agent.enable_separate_color_nets();
+        }
+
+        if train_options.restrict_to_candidates {
+            agent.set_candidate_distance(Some(1));
+        }
+
+        // one network per color, or a single shared one trained on the steps of both colors
+        let turns = if agent.has_separate_color_nets() {
+            vec![Some(Turn::Black), Some(Turn::White)]
         } else {
-            Turn::White
+            vec![None]
         };
+        let mut nets = Vec::with_capacity(turns.len());
+
+        for turn in turns {
+            let turn_or_black = turn.unwrap_or(Turn::Black);
+            let model = agent.model_for(turn_or_black);
+            let var_store = agent.var_store_for(turn_or_black);
+
+            let mut target =
+                Model::new(var_store.root().sub("train-target"), model.config().clone());
+            target.copy_weights_from(model, None);
+
+            let optimizer = train_options.optimizer.build(
+                var_store,
+                train_options.learning_rate,
+                train_options.weight_decay,
+            )?;
+
+            nets.push((turn, target, optimizer));
+        }
+
+        let mut rng = rand::thread_rng();
         let mut epsilon = train_options.epsilon;
-        let mut replay_buffer = VecDeque::with_capacity(train_options.replay_buffer_size);
+        let mut replay_buffer =
+            ReplayBuffer::with_eviction(train_options.replay_buffer_size, train_options.eviction);
+
+        for step in generate_demonstrations(
+            15,
+            5,
+            train_options.demonstration_games,
+            &train_options.reward,
+        ) {
+            replay_buffer.push(step);
+        }
+
         let mut loss_visualizer = loss_visualizer::LossVisualizer::new();
+        let mut color_balancer = color_balancer::ColorBalancer::new();
+        let mut curriculum = curriculum::Curriculum::new(train_options.curriculum.clone());
+        let mut stream = ReplayStream::new(
+            agent,
+            curriculum.opponent(),
+            ReplayConfig {
+                board_size: 15,
+                win_len: 5,
+                exploration: Exploration {
+                    epsilon,
+                    focused: train_options.focused_exploration
+                        || train_options.restrict_to_candidates,
+                },
+                reward: train_options.reward.clone(),
+                black_probability: 0.5,
+                penalize_illegal: train_options.penalize_illegal,
+            },
+        );
+        let mut option_loggers: Vec<Box<dyn TrainLogger>> = vec![];
 
-        for epoch in 0..epoches {
-            println!("epoches: {}", epoch + 1);
+        if let Some(logdir) = &train_options.tb_logdir {
+            option_loggers.push(Box::new(TensorBoardLogger::new(logdir)?));
+        }
+
+        if let Some(plot_path) = &train_options.plot_path {
+            option_loggers.push(Box::new(PlotLogger::new(plot_path)));
+        }
 
+        for epoch in 0..epoches {
             let mut iteration = 0;
+            let mut entropy_sum = 0.0;
+            let mut entropy_count = 0;
+
+            stream.set_opponent(curriculum.opponent());
+            // the evaluation games of the previous epoch don't count
+            stream.agent_mut().take_policy_entropy();
 
             while iteration < train_options.iterations {
-                let (new_game, new_agent_turn, replay_step) =
-                    sample_replay(game, agent_turn, agent, Opponent::Random, epsilon);
+                let config = stream.config_mut();
+                config.exploration.epsilon = epsilon;
+                config.black_probability = if train_options.adaptive_color {
+                    color_balancer.black_probability()
+                } else {
+                    0.5
+                };
+
+                let replay_step = stream.next().unwrap();
+
+                // the entropy of the agent's last greedy move, from the values it already evaluated
+                if let Some(entropy) = stream.agent_mut().take_policy_entropy() {
+                    entropy_sum += entropy as f64;
+                    entropy_count += 1;
+                }
+                let agent_turn = stream.agent_turn();
 
-                game = new_game;
-                agent_turn = new_agent_turn;
+                if let Some(game_result) = replay_step.game_result {
+                    color_balancer.add(agent_turn, game_result == GameResult::Win(agent_turn));
+                }
 
                 // skip if the turn is not the agent's turn
                 if replay_step.turn != agent_turn {
                     continue;
                 }
 
-                if !replay_buffer.is_empty()
-                    && train_options.replay_buffer_size <= replay_buffer.len()
-                {
-                    replay_buffer.pop_front();
-                }
-
-                replay_buffer.push_back(replay_step);
+                let priority = replay_step.reward.abs() as f64;
+                replay_buffer.push_with_priority(replay_step, priority);
 
                 epsilon *= train_options.epsilon_decay;
                 epsilon = epsilon.max(train_options.epsilon_min);
@@ -107,49 +423,134 @@ impl GomokuDDQNTrainer {
                 iteration += 1;
             }
 
-            for _ in 0..train_options.training_steps {
-                let batch = if train_options.batch_size <= replay_buffer.len() {
-                    replay_buffer
-                        .iter()
-                        .choose_multiple(&mut rng, train_options.batch_size)
-                } else {
-                    replay_buffer.iter().collect()
-                };
+            let agent = stream.agent_mut();
 
-                optimizer.zero_grad();
+            for step in 0..train_options.training_steps {
+                let batch = replay_buffer
+                    .sample(
+                        &mut rng,
+                        train_options.batch_size,
+                        train_options.demonstration_weight,
+                        train_options.terminal_oversample,
+                    )
+                    .map_err(|err| {
+                        figment::Error::from(format!("can't sample the replay buffer: {}", err))
+                    })?;
 
-                let loss = loss::compute_loss(agent.model(), &target, &batch, train_options.gamma);
-                loss.backward();
+                for (turn, target, optimizer) in &mut nets {
+                    let batch = Vec::from_iter(
+                        batch
+                            .iter()
+                            .copied()
+                            .filter(|step| turn.is_none_or(|turn| step.turn == turn)),
+                    );
 
-                optimizer.clip_grad_norm(train_options.max_grad_norm);
-                optimizer.step();
+                    if batch.is_empty() {
+                        continue;
+                    }
+
+                    let model = agent.model_for(turn.unwrap_or(Turn::Black));
+
+                    optimizer.zero_grad();
 
-                target.copy_weights_from(agent.model(), Some(train_options.tau));
+                    let loss = loss::compute_loss(
+                        model,
+                        target,
+                        &batch,
+                        train_options.gamma,
+                        train_options.td_target,
+                        epsilon,
+                        agent.candidate_distance(),
+                    );
+                    loss.backward();
 
-                loss_visualizer.add(loss.double_value(&[]));
+                    optimizer.clip_grad_norm(train_options.max_grad_norm);
+                    optimizer.step();
+
+                    target.copy_weights_from(model, Some(train_options.tau));
+
+                    if train_options.sync_target_bn_stats {
+                        target.copy_bn_stats_from(model);
+                    }
+
+                    let loss = loss.double_value(&[]);
+                    loss_visualizer.add(loss);
+
+                    let report = StepReport { epoch, step, loss };
+                    for logger in self.loggers.iter_mut().chain(&mut option_loggers) {
+                        logger.on_step(&report);
+                    }
+                }
             }
 
-            println!("loss: {}", loss_visualizer.mean());
+            // cached outputs are stale now that the weights have changed
+            agent.reset();
+
+            let policy_entropy = if entropy_count == 0 {
+                0.0
+            } else {
+                entropy_sum / entropy_count as f64
+            };
 
             if let Some(save_path) = &train_options.save_path {
                 if let Err(err) = agent.save(save_path) {
-                    eprintln!("failed to save agent: {:#?}", err);
+                    for logger in self.loggers.iter_mut().chain(&mut option_loggers) {
+                        logger.on_save_failed(save_path, &err);
+                    }
                 }
             }
 
-            let (agent_wins, opponent_wins, draws) = eval::evaluate_many(agent, 10);
-            println!(
-                "agent wins: {}, opponent wins: {}, draws: {}",
-                agent_wins, opponent_wins, draws
-            );
+            let tally =
+                eval::evaluate_many(agent, curriculum.opponent(), train_options.eval_games)?;
+            let probe_accuracy = match &probe_set {
+                Some(probe_set) => Some(probe_set.accuracy(agent)?),
+                None => None,
+            };
+
+            let report = EpochReport {
+                epoch,
+                loss: loss_visualizer.mean(),
+                policy_entropy,
+                epsilon,
+                unique_positions: count_unique_positions(replay_buffer.iter()),
+                replay_buffer_len: replay_buffer.len(),
+                agent_wins: tally.agent_wins(),
+                opponent_wins: tally.games() - tally.agent_wins() - tally.draws,
+                draws: tally.draws,
+                black_wins: tally.black_wins,
+                black_games: tally.black_games,
+                white_wins: tally.white_wins,
+                white_games: tally.white_games,
+                mean_game_length: tally.mean_game_length,
+                probe_accuracy,
+            };
+            for logger in self.loggers.iter_mut().chain(&mut option_loggers) {
+                logger.on_epoch(&report);
+            }
+
+            if curriculum.advance(tally.agent_wins(), tally.games()) {
+                for logger in self.loggers.iter_mut().chain(&mut option_loggers) {
+                    logger.on_curriculum_advanced(epoch, curriculum.opponent());
+                }
+            }
 
-            if epoch % 10 == 0 {
-                let (agent_turn, recent_game, _) = eval::evaluate(agent);
-                println!(
-                    "recent game [agent={}]:\n{}",
-                    agent_turn.name(),
-                    recent_game
-                );
+            if train_options.shows_recent_game(epoch) {
+                let (agent_turn, recent_game, _) = eval::evaluate(agent, curriculum.opponent())?;
+                let moves =
+                    Vec::from_iter(
+                        recent_game.moves().iter().zip(recent_game.history()).map(
+                            |(&index, (turn, _))| format_move(recent_game.board(), index, *turn),
+                        ),
+                    );
+                let report = RecentGameReport {
+                    epoch,
+                    agent_turn,
+                    game: recent_game,
+                    moves,
+                };
+                for logger in self.loggers.iter_mut().chain(&mut option_loggers) {
+                    logger.on_recent_game(&report);
+                }
             }
         }
 
@@ -158,10 +559,15 @@ impl GomokuDDQNTrainer {
 }
 
 mod loss {
+    use super::TdTargetKind;
     use crate::{
-        agents::gomoku_ddqn::model::{encode_batched_board, Model},
+        agents::gomoku_ddqn::{
+            agent::allowed_moves,
+            model::{encode_batched_board, Model},
+        },
         replay::ReplayStep,
     };
+    use gomoku_core::board::Board;
     use tch::{nn::ModuleT, Device, Kind, Tensor};
 
     pub fn compute_loss(
@@ -169,63 +575,88 @@ mod loss {
         target: &Model,
         batch: &[&ReplayStep],
         gamma: f64,
+        td_target: TdTargetKind,
+        epsilon: f64,
+        candidate_distance: Option<usize>,
     ) -> Tensor {
-        let td_target = compute_td_target(agent, target, batch, gamma);
+        let td_target = compute_td_target(
+            agent,
+            target,
+            batch,
+            gamma,
+            td_target,
+            epsilon,
+            candidate_distance,
+        );
 
         let boards = Vec::from_iter(batch.iter().map(|step| &step.boards));
-        let boards = encode_batched_board(&boards);
+        let boards = encode_batched_board(&boards, agent.config());
         let q = agent.forward_t(&boards, false).to_device(Device::Cpu);
 
         let actions = Vec::from_iter(batch.iter().map(|step| step.action as i64));
-        let actions = Tensor::from_slice(&actions);
-        let q = q.index_select(1, &actions);
+        let actions = Tensor::from_slice(&actions).view([-1, 1]);
+        let q = action_values(&q, &actions);
 
         (td_target - q).square().mean(Kind::Float)
     }
 
+    /// Picks each sample's Q-value for its own action.
+    ///
+    /// `qs` has shape `[batch, actions]` and `actions` has shape `[batch, 1]`; the result has shape
+    /// `[batch, 1]`, matching the TD target.
+    fn action_values(qs: &Tensor, actions: &Tensor) -> Tensor {
+        qs.gather(1, actions, false)
+    }
+
     fn compute_td_target(
         agent: &Model,
         target: &Model,
         batch: &[&ReplayStep],
         gamma: f64,
+        td_target: TdTargetKind,
+        epsilon: f64,
+        candidate_distance: Option<usize>,
     ) -> Tensor {
         let r = Vec::from_iter(batch.iter().map(|step| step.reward as f64));
         let r = Tensor::from_slice(&r).view([-1, 1]);
 
         // NOTE: it is safe to fall back to the current board if the next board is not available,
         // because those wrong q values will be masked out by flags later
-        let next_boards = encode_batched_board(&Vec::from_iter(
-            batch
-                .iter()
-                .map(|step| step.next_boards.as_ref().unwrap_or(&step.boards)),
-        ));
+        let next_boards = encode_batched_board(
+            &Vec::from_iter(
+                batch
+                    .iter()
+                    .map(|step| step.next_boards.as_ref().unwrap_or(&step.boards)),
+            ),
+            agent.config(),
+        );
         let action_values = agent.forward_t(&next_boards, false).to_device(Device::Cpu);
         let action_values: Vec<f64> = action_values.flatten(0, -1).try_into().unwrap();
 
         let mut legal_actions = Vec::with_capacity(batch.len());
+        let mut legal_mask = Vec::with_capacity(action_values.len());
 
-        // apply argmax only to legal moves
+        // apply argmax only to the moves legal in the next state, whose values were computed above
         for (i, step) in batch.iter().enumerate() {
-            let board = &step.boards.last().unwrap().1;
+            let board = &step
+                .next_boards
+                .as_ref()
+                .unwrap_or(&step.boards)
+                .last()
+                .unwrap()
+                .1;
             let action_values = &action_values[i * board.board_size() * board.board_size()
                 ..(i + 1) * board.board_size() * board.board_size()];
-            let pairs = Vec::from_iter(
-                board
-                    .legal_moves()
-                    .into_iter()
-                    .map(|action| (action, action_values[action])),
-            );
-            let best_action = pairs
-                .iter()
-                .max_by(|(_, lhs), (_, rhs)| f64::total_cmp(lhs, rhs))
-                .unwrap()
-                .0;
+
+            let (best_action, mask) = greedy_action(board, action_values, candidate_distance);
             legal_actions.push(best_action as i64);
+            legal_mask.extend(mask);
         }
 
         let actions = Tensor::from_slice(&legal_actions).view([-1, 1]);
+        let legal_mask = Tensor::from_slice(&legal_mask).view([batch.len() as i64, -1]);
         let target_qs = target.forward_t(&next_boards, false).to_device(Device::Cpu);
-        let target_q = target_qs.gather(1, &actions, false);
+        let target_q = next_state_values(td_target, &target_qs, &actions, &legal_mask, epsilon);
 
         // flag for whether the game is done to mask out the future q values
         let is_done =
@@ -238,6 +669,128 @@ mod loss {
 
         r + (1.0 - is_done) * gamma * target_q
     }
+
+    /// Picks the agent's best action on `board` from its `action_values`, one per cell, and returns
+    /// it along with a mask marking the actions it may choose from with `1`: the legal ones, or only
+    /// the candidates within `candidate_distance` of a stone, like `next_move`.
+    fn greedy_action(
+        board: &Board,
+        action_values: &[f64],
+        candidate_distance: Option<usize>,
+    ) -> (usize, Vec<f64>) {
+        let allowed = allowed_moves(board, candidate_distance);
+
+        let mut mask = vec![0f64; action_values.len()];
+        for &action in &allowed {
+            mask[action] = 1f64;
+        }
+
+        let best_action = allowed
+            .into_iter()
+            .max_by(|&lhs, &rhs| f64::total_cmp(&action_values[lhs], &action_values[rhs]))
+            .unwrap();
+
+        (best_action, mask)
+    }
+
+    /// Computes the value of each next state from the target network's Q-values.
+    ///
+    /// `greedy_actions` are the agent's best legal actions (shape `[batch, 1]`) and `legal_mask`
+    /// marks the legal actions with `1` (shape `[batch, actions]`).
+    fn next_state_values(
+        td_target: TdTargetKind,
+        target_qs: &Tensor,
+        greedy_actions: &Tensor,
+        legal_mask: &Tensor,
+        epsilon: f64,
+    ) -> Tensor {
+        let greedy_q = action_values(target_qs, greedy_actions);
+
+        match td_target {
+            TdTargetKind::QLearning => greedy_q,
+            TdTargetKind::ExpectedSarsa => {
+                // epsilon-greedy: a uniformly random legal action with probability epsilon,
+                // the greedy action otherwise
+                let legal_count = legal_mask.sum_dim_intlist(1, true, Kind::Float);
+                let mean_legal_q = (target_qs * legal_mask).sum_dim_intlist(1, true, Kind::Float)
+                    / legal_count.clamp_min(1.0);
+
+                greedy_q * (1.0 - epsilon) + mean_legal_q * epsilon
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use gomoku_core::board::Cell;
+
+        #[test]
+        fn test_action_values() {
+            let qs = Tensor::from_slice(&[1f32, 2f32, 3f32, 4f32, 5f32, 6f32, 7f32, 8f32, 9f32])
+                .view([3, 3]);
+            let actions = Tensor::from_slice(&[2i64, 0i64, 1i64]).view([-1, 1]);
+
+            let q = action_values(&qs, &actions);
+            assert_eq!(q.size(), &[3, 1]);
+
+            let q: Vec<f32> = q.view([-1]).try_into().unwrap();
+
+            assert_eq!(q, vec![3f32, 4f32, 8f32]);
+        }
+
+        #[test]
+        fn test_greedy_action() {
+            let mut board = Board::new(15);
+            board.set_cell(112, Cell::Black);
+
+            // the far corner looks best, but isn't a candidate
+            let mut action_values = vec![0f64; 15 * 15];
+            action_values[0] = 10.0;
+            action_values[128] = 1.0;
+
+            let (action, mask) = greedy_action(&board, &action_values, None);
+            assert_eq!(action, 0);
+            assert_eq!(mask.iter().sum::<f64>(), 224.0);
+            assert_eq!(mask[112], 0.0);
+
+            let (action, mask) = greedy_action(&board, &action_values, Some(1));
+            assert_eq!(action, 128);
+            assert_eq!(mask.iter().sum::<f64>(), 8.0);
+            assert_eq!(mask[0], 0.0);
+
+            // no move is within a distance of zero, so every legal move is allowed
+            let (action, mask) = greedy_action(&board, &action_values, Some(0));
+            assert_eq!(action, 0);
+            assert_eq!(mask.iter().sum::<f64>(), 224.0);
+        }
+
+        #[test]
+        fn test_next_state_values() {
+            let target_qs = Tensor::from_slice(&[1f32, 2f32, 3f32, 4f32]).view([1, 4]);
+            let greedy_actions = Tensor::from_slice(&[2i64]).view([1, 1]);
+            // the last action is illegal, so it must not contribute to the expectation
+            let legal_mask = Tensor::from_slice(&[1f32, 1f32, 1f32, 0f32]).view([1, 4]);
+
+            let q_learning = next_state_values(
+                TdTargetKind::QLearning,
+                &target_qs,
+                &greedy_actions,
+                &legal_mask,
+                0.5,
+            );
+            let expected_sarsa = next_state_values(
+                TdTargetKind::ExpectedSarsa,
+                &target_qs,
+                &greedy_actions,
+                &legal_mask,
+                0.5,
+            );
+
+            assert_eq!(q_learning.double_value(&[0, 0]), 3.0);
+            assert_eq!(expected_sarsa.double_value(&[0, 0]), 2.5);
+        }
+    }
 }
 
 mod loss_visualizer {
@@ -268,25 +821,180 @@ mod loss_visualizer {
     }
 }
 
-mod eval {
-    use crate::{agent::Agent, agents::gomoku_ddqn::agent::GomokuDDQNAgent};
+mod color_balancer {
+    use gomoku_core::game::Turn;
+    use std::collections::VecDeque;
+
+    const WINDOW: usize = 100;
+
+    /// Tracks the agent's recent win-rate per color to decide which color it should play next.
+    pub struct ColorBalancer {
+        black: VecDeque<bool>,
+        white: VecDeque<bool>,
+    }
+
+    impl ColorBalancer {
+        pub fn new() -> Self {
+            Self {
+                black: VecDeque::with_capacity(WINDOW),
+                white: VecDeque::with_capacity(WINDOW),
+            }
+        }
+
+        pub fn add(&mut self, agent_turn: Turn, won: bool) {
+            let results = match agent_turn {
+                Turn::Black => &mut self.black,
+                Turn::White => &mut self.white,
+            };
+
+            if WINDOW <= results.len() {
+                results.pop_front();
+            }
+
+            results.push_back(won);
+        }
+
+        /// Returns the probability of the agent playing black.
+        ///
+        /// It is `0.5` when both colors perform equally, and shifts towards the color with the
+        /// lower win-rate by half the win-rate gap, clamped to `[0.1, 0.9]`.
+        pub fn black_probability(&self) -> f64 {
+            let black = win_rate(&self.black);
+            let white = win_rate(&self.white);
+
+            (0.5 + (white - black) / 2.0).clamp(0.1, 0.9)
+        }
+    }
+
+    fn win_rate(results: &VecDeque<bool>) -> f64 {
+        if results.is_empty() {
+            return 0.5;
+        }
+
+        results.iter().filter(|&&won| won).count() as f64 / results.len() as f64
+    }
+}
+
+mod curriculum {
+    use super::CurriculumStage;
+    use crate::replay::Opponent;
+
+    /// Tracks the current stage of the opponent curriculum.
+    pub struct Curriculum {
+        stages: Vec<CurriculumStage>,
+        current: usize,
+    }
+
+    impl Curriculum {
+        /// Panics on an empty list, which `TrainOptions::validate` rejects beforehand.
+        pub fn new(stages: Vec<CurriculumStage>) -> Self {
+            assert!(
+                !stages.is_empty(),
+                "curriculum must have at least one stage"
+            );
+
+            Self { stages, current: 0 }
+        }
+
+        pub fn opponent(&self) -> Opponent {
+            self.stages[self.current].opponent
+        }
+
+        /// Moves on to the next stage if the agent won more than the current stage's threshold of
+        /// the evaluation games. Returns whether the stage changed.
+        pub fn advance(&mut self, agent_wins: usize, games: usize) -> bool {
+            if games == 0 || self.current + 1 == self.stages.len() {
+                return false;
+            }
+
+            let win_rate = agent_wins as f64 / games as f64;
+
+            if win_rate <= self.stages[self.current].win_rate_threshold {
+                return false;
+            }
+
+            self.current += 1;
+            true
+        }
+    }
+}
+
+pub(super) mod eval {
+    use crate::{
+        agent::{Agent, AgentError},
+        replay::{opponent_move, Opponent},
+    };
     use gomoku_core::game::{Game, GameResult, Turn};
-    use rand::{seq::SliceRandom, Rng};
+    use rand::Rng;
+
+    /// The board the evaluation games are played on.
+    const BOARD_SIZE: usize = 15;
+    const WIN_LEN: usize = 5;
+
+    /// The outcome of a batch of evaluation games, from the agent's point of view.
+    ///
+    /// The agent won `black_wins` of the `black_games` it played as black, and likewise as white;
+    /// the games of both colors include the draws.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct EvalTally {
+        pub black_wins: usize,
+        pub black_games: usize,
+        pub white_wins: usize,
+        pub white_games: usize,
+        pub draws: usize,
+        pub mean_game_length: f64,
+    }
+
+    impl EvalTally {
+        /// The games the agent won, playing either color.
+        pub fn agent_wins(&self) -> usize {
+            self.black_wins + self.white_wins
+        }
+
+        /// The games played, including the draws.
+        pub fn games(&self) -> usize {
+            self.black_games + self.white_games
+        }
+    }
+
+    /// Plays `n` games against the given opponent and tallies them; see `tally`.
+    pub fn evaluate_many(
+        agent: &mut dyn Agent,
+        opponent: Opponent,
+        n: usize,
+    ) -> Result<EvalTally, AgentError> {
+        let games = (0..n)
+            .map(|_| {
+                let (agent_turn, game, game_result) = evaluate(agent, opponent)?;
+                Ok((agent_turn, game.turn_count(), game_result))
+            })
+            .collect::<Result<Vec<_>, AgentError>>()?;
+
+        Ok(tally(games))
+    }
 
-    pub fn evaluate_many(agent: &mut GomokuDDQNAgent, n: usize) -> (usize, usize, usize) {
-        let mut agent_wins = 0;
-        let mut opponent_wins = 0;
+    /// Aggregates `(agent_turn, game_length, game_result)` records of finished games.
+    pub fn tally(games: impl IntoIterator<Item = (Turn, usize, GameResult)>) -> EvalTally {
+        let mut black_wins = 0;
+        let mut black_games = 0;
+        let mut white_wins = 0;
+        let mut white_games = 0;
         let mut draws = 0;
+        let mut total_length = 0;
 
-        for _ in 0..n {
-            let (agent_turn, _, game_result) = evaluate(agent);
+        for (agent_turn, game_length, game_result) in games {
+            total_length += game_length;
+
+            let (wins, games) = match agent_turn {
+                Turn::Black => (&mut black_wins, &mut black_games),
+                Turn::White => (&mut white_wins, &mut white_games),
+            };
+            *games += 1;
 
             match game_result {
                 GameResult::Win(winner) => {
                     if winner == agent_turn {
-                        agent_wins += 1;
-                    } else {
-                        opponent_wins += 1;
+                        *wins += 1;
                     }
                 }
                 GameResult::Draw => {
@@ -295,12 +1003,57 @@ mod eval {
             }
         }
 
-        (agent_wins, opponent_wins, draws)
+        let games = black_games + white_games;
+        let mean_game_length = if games == 0 {
+            0.0
+        } else {
+            total_length as f64 / games as f64
+        };
+
+        EvalTally {
+            black_wins,
+            black_games,
+            white_wins,
+            white_games,
+            draws,
+            mean_game_length,
+        }
     }
 
-    pub fn evaluate(agent: &mut GomokuDDQNAgent) -> (Turn, Game, GameResult) {
+    /// Plays `n` games against the given opponent and counts how often each cell was played.
+    ///
+    /// The agent is reset before every game. Returns a histogram with one entry per cell of the
+    /// board, summing to the total number of moves played.
+    pub fn move_heatmap(
+        agent: &mut dyn Agent,
+        opponent: Opponent,
+        n: usize,
+    ) -> Result<Vec<usize>, AgentError> {
+        let mut heatmap = vec![0; BOARD_SIZE * BOARD_SIZE];
+
+        for _ in 0..n {
+            agent.reset();
+            let (_, game, _) = evaluate(agent, opponent)?;
+
+            for &index in game.moves() {
+                heatmap[index] += 1;
+            }
+        }
+
+        Ok(heatmap)
+    }
+
+    /// Plays one game against the given opponent, with the agent's color drawn at random.
+    ///
+    /// Returns the agent's color, the finished game and its result. An agent finding no legal
+    /// move ends the game as a draw; any other error of the agent, or a move onto an occupied
+    /// cell, is returned.
+    pub fn evaluate(
+        agent: &mut dyn Agent,
+        opponent: Opponent,
+    ) -> Result<(Turn, Game, GameResult), AgentError> {
         let mut rng = rand::thread_rng();
-        let mut game = Game::new(15, 5);
+        let mut game = Game::new(BOARD_SIZE, WIN_LEN);
         let agent_turn = if rng.gen_bool(0.5) {
             Turn::Black
         } else {
@@ -309,18 +1062,467 @@ mod eval {
 
         while game.game_result().is_none() {
             let action = if game.turn() == agent_turn {
-                agent.next_move(&game).unwrap()
+                match agent.next_move(&game) {
+                    Ok(action) => action,
+                    Err(AgentError::NoLegalMoves) => {
+                        return Ok((agent_turn, game, GameResult::Draw))
+                    }
+                    Err(err) => return Err(err),
+                }
             } else {
-                *game.board().legal_moves().choose(&mut rng).unwrap()
+                opponent_move(opponent, agent, &game)
             };
 
-            let result = game.place_stone(action).unwrap();
+            let result = game.place_stone(action)?;
 
             if let Some(game_result) = result.game_result {
-                return (agent_turn, game, game_result);
+                return Ok((agent_turn, game, game_result));
+            }
+        }
+
+        Ok((agent_turn, game, GameResult::Draw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{
+        gomoku_ddqn::{EncodingMode, ModelConfig, NormKind},
+        scripted::ScriptedAgent,
+    };
+    use figment::providers::{Format, Toml};
+    use gomoku_core::game::Game;
+    use std::{cell::RefCell, rc::Rc};
+    use tch::{
+        kind::FLOAT_CPU,
+        nn::{linear, Module},
+        Device, Kind, Tensor,
+    };
+
+    #[test]
+    fn test_sgd_optimizer_step() {
+        let var_store = VarStore::new(Device::Cpu);
+        let layer = linear(var_store.root(), 4, 1, Default::default());
+        let mut optimizer = OptimizerKind::Sgd {
+            momentum: 0.9,
+            nesterov: false,
+        }
+        .build(&var_store, 0.01, 0.0)
+        .unwrap();
+
+        let loss = layer
+            .forward(&Tensor::ones([2, 4], FLOAT_CPU))
+            .square()
+            .mean(Kind::Float);
+        optimizer.backward_step(&loss);
+    }
+
+    #[test]
+    fn test_weight_decay() {
+        let var_store = VarStore::new(Device::Cpu);
+        let layer = linear(var_store.root(), 4, 1, Default::default());
+        let options: TrainOptions = Figment::new()
+            .merge(Toml::string("weight_decay = 0.1"))
+            .extract()
+            .unwrap();
+        let mut optimizer = options
+            .optimizer
+            .build(&var_store, 0.01, options.weight_decay)
+            .unwrap();
+
+        let norm_before = layer.ws.abs().sum(Kind::Float).double_value(&[]);
+
+        // a zero input gives the weights a zero gradient, leaving only the decay
+        let loss = layer
+            .forward(&Tensor::zeros([2, 4], FLOAT_CPU))
+            .sum(Kind::Float);
+        optimizer.backward_step(&loss);
+
+        let norm_after = layer.ws.abs().sum(Kind::Float).double_value(&[]);
+        assert!(norm_after < norm_before);
+    }
+
+    #[test]
+    fn test_save_resolved() {
+        let dir = std::env::temp_dir().join(format!("gomoku-train-options-{}", std::process::id()));
+        let save_path = dir.join("agent.safetensors");
+        let save_path = save_path.to_str().unwrap();
+
+        for toml in [
+            "batch_size = 7\nrecent_game_every = 0\nweight_decay = 0.25\n[optimizer]\nkind = \"sgd\"\nmomentum = 0.5\nnesterov = true",
+            "eviction = \"reservoir\"\nplot_path = \"curves.png\"\n[reward]\nstep_penalty = 0.125\n[[curriculum]]\nopponent = \"heuristic\"\nwin_rate_threshold = 0.6\n[[curriculum]]\nopponent = \"self_play\"\nwin_rate_threshold = 0.0",
+        ] {
+            let options: TrainOptions = Figment::new()
+                .merge(Toml::string(toml))
+                .extract()
+                .unwrap();
+
+            let path = options.save_resolved(save_path).unwrap();
+            assert_eq!(path, dir.join("train_options.toml"));
+
+            let resolved: TrainOptions = Figment::new()
+                .merge(Toml::file(&path))
+                .extract()
+                .unwrap();
+            assert_eq!(resolved, options);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let options: TrainOptions = Figment::new()
+            .merge(Toml::string("curriculum = []"))
+            .extract()
+            .unwrap();
+        assert!(matches!(options.validate(), Err(AgentError::Config(_))));
+
+        for terminal_oversample in [-1.0, f64::NAN, f64::INFINITY] {
+            let options = TrainOptions {
+                terminal_oversample,
+                ..Default::default()
+            };
+            assert!(matches!(options.validate(), Err(AgentError::Config(_))));
+
+            let options = TrainOptions {
+                demonstration_weight: terminal_oversample,
+                ..Default::default()
+            };
+            assert!(matches!(options.validate(), Err(AgentError::Config(_))));
+        }
+
+        let options: TrainOptions = Figment::new()
+            .merge(Toml::string("terminal_oversample = nan"))
+            .extract()
+            .unwrap();
+        assert!(matches!(options.validate(), Err(AgentError::Config(_))));
+
+        assert!(TrainOptions::default().validate().is_ok());
+        let options = TrainOptions {
+            terminal_oversample: 0.0,
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_recent_game_every() {
+        let options = TrainOptions::default();
+        let shown = (0..25)
+            .filter(|&epoch| options.shows_recent_game(epoch))
+            .collect::<Vec<_>>();
+        assert_eq!(shown, vec![0, 10, 20]);
+
+        let options = TrainOptions {
+            recent_game_every: Some(3),
+            ..Default::default()
+        };
+        let shown = (0..10)
+            .filter(|&epoch| options.shows_recent_game(epoch))
+            .collect::<Vec<_>>();
+        assert_eq!(shown, vec![0, 3, 6, 9]);
+
+        let options = TrainOptions {
+            recent_game_every: None,
+            ..Default::default()
+        };
+        assert!((0..10).all(|epoch| !options.shows_recent_game(epoch)));
+    }
+
+    #[test]
+    fn test_color_balancer() {
+        let mut balancer = color_balancer::ColorBalancer::new();
+        assert_eq!(balancer.black_probability(), 0.5);
+
+        // the agent wins every game as black, but only a quarter as white
+        for i in 0..20 {
+            balancer.add(Turn::Black, true);
+            balancer.add(Turn::White, i % 4 == 0);
+        }
+
+        assert!(balancer.black_probability() < 0.5);
+        assert_eq!(balancer.black_probability(), 0.5 + (0.25 - 1.0) / 2.0);
+    }
+
+    #[test]
+    fn test_curriculum() {
+        let stages = vec![
+            CurriculumStage {
+                opponent: Opponent::Random,
+                win_rate_threshold: 0.8,
+            },
+            CurriculumStage {
+                opponent: Opponent::Heuristic,
+                win_rate_threshold: 0.6,
+            },
+            CurriculumStage {
+                opponent: Opponent::SelfPlay,
+                win_rate_threshold: 0.0,
+            },
+        ];
+        let mut curriculum = curriculum::Curriculum::new(stages);
+        let games = |agent_wins: usize| {
+            let results = (0..10).map(|i| {
+                let winner = if i < agent_wins {
+                    Turn::Black
+                } else {
+                    Turn::White
+                };
+                (Turn::Black, 20, GameResult::Win(winner))
+            });
+            let tally = eval::tally(results);
+            (tally.agent_wins(), tally.games())
+        };
+
+        // a low win-rate keeps the current opponent
+        let (agent_wins, total) = games(5);
+        assert!(!curriculum.advance(agent_wins, total));
+        assert_eq!(curriculum.opponent(), Opponent::Random);
+
+        // a win-rate at the threshold doesn't exceed it
+        let (agent_wins, total) = games(8);
+        assert!(!curriculum.advance(agent_wins, total));
+        assert_eq!(curriculum.opponent(), Opponent::Random);
+
+        let (agent_wins, total) = games(9);
+        assert!(curriculum.advance(agent_wins, total));
+        assert_eq!(curriculum.opponent(), Opponent::Heuristic);
+
+        let (agent_wins, total) = games(7);
+        assert!(curriculum.advance(agent_wins, total));
+        assert_eq!(curriculum.opponent(), Opponent::SelfPlay);
+
+        // the last stage is never left
+        let (agent_wins, total) = games(10);
+        assert!(!curriculum.advance(agent_wins, total));
+        assert_eq!(curriculum.opponent(), Opponent::SelfPlay);
+    }
+
+    #[test]
+    fn test_eval_games() {
+        /// Plays the lowest-index legal move and counts the games it takes part in.
+        struct CountingAgent {
+            games: usize,
+        }
+
+        impl Agent for CountingAgent {
+            fn save(&self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+                // the agent's first move of a game is one of the first two moves
+                if game.moves().len() < 2 {
+                    self.games += 1;
+                }
+
+                Ok(game.board().legal_moves()[0])
             }
         }
 
-        (agent_turn, game, GameResult::Draw)
+        let options = Figment::new().merge(Toml::string("eval_games = 7"));
+        let train_options: TrainOptions = options.extract().unwrap();
+        let mut agent = CountingAgent { games: 0 };
+
+        let tally =
+            eval::evaluate_many(&mut agent, Opponent::Random, train_options.eval_games).unwrap();
+
+        assert_eq!(agent.games, 7);
+        assert_eq!(tally.games(), 7);
+    }
+
+    #[test]
+    fn test_eval_tally() {
+        let games = [
+            (Turn::Black, 9, GameResult::Win(Turn::Black)),
+            (Turn::White, 20, GameResult::Win(Turn::Black)),
+            (Turn::White, 225, GameResult::Draw),
+            (Turn::Black, 10, GameResult::Win(Turn::White)),
+        ];
+
+        let tally = eval::tally(games);
+        assert_eq!(
+            tally,
+            eval::EvalTally {
+                black_wins: 1,
+                black_games: 2,
+                white_wins: 0,
+                white_games: 2,
+                draws: 1,
+                mean_game_length: 66.0,
+            }
+        );
+        assert_eq!((tally.agent_wins(), tally.games()), (1, 4));
+
+        let empty = eval::tally([]);
+        assert_eq!(
+            (empty.games(), empty.draws, empty.mean_game_length),
+            (0, 0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_many_by_color() {
+        // playing both sides, the agent always fills the lowest empty cell, so every game has
+        // the same winner whichever color the agent was given
+        let mut agent = ScriptedAgent::new(vec![]);
+        let tally = eval::evaluate_many(&mut agent, Opponent::SelfPlay, 20).unwrap();
+
+        assert_eq!(tally.games(), 20);
+        assert_eq!(tally.draws, 0);
+        assert!(
+            (tally.black_wins, tally.white_wins) == (tally.black_games, 0)
+                || (tally.black_wins, tally.white_wins) == (0, tally.white_games)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        struct FailingAgent {
+            error: fn(&Game) -> Result<usize, AgentError>,
+        }
+
+        impl Agent for FailingAgent {
+            fn save(&self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+                (self.error)(game)
+            }
+        }
+
+        let mut agent = FailingAgent {
+            error: |_| Err(AgentError::NoLegalMoves),
+        };
+        let (_, _, game_result) = eval::evaluate(&mut agent, Opponent::Random).unwrap();
+        assert_eq!(game_result, GameResult::Draw);
+
+        let mut agent = FailingAgent {
+            error: |_| {
+                Err(AgentError::Unsupported {
+                    operation: "testing",
+                })
+            },
+        };
+        assert!(matches!(
+            eval::evaluate(&mut agent, Opponent::Random),
+            Err(AgentError::Unsupported { .. })
+        ));
+        assert!(eval::evaluate_many(&mut agent, Opponent::Random, 3).is_err());
+
+        // plays onto the opponent's stone, or the center twice when moving first
+        let mut agent = FailingAgent {
+            error: |game| Ok(game.moves().first().copied().unwrap_or(112)),
+        };
+        let result = (0..10).find_map(|_| eval::evaluate(&mut agent, Opponent::Random).err());
+        assert!(matches!(result, Some(AgentError::IllegalMove(_))));
+    }
+
+    #[test]
+    fn test_move_heatmap() {
+        // black wins along row 7 while white fills the start of row 0
+        let script = vec![112, 0, 113, 1, 114, 2, 115, 3, 116];
+        let mut agent = ScriptedAgent::new(script.clone());
+
+        let heatmap = eval::move_heatmap(&mut agent, Opponent::SelfPlay, 4).unwrap();
+
+        assert_eq!(heatmap.len(), 15 * 15);
+        assert_eq!(heatmap.iter().sum::<usize>(), 4 * script.len());
+
+        for (index, &count) in heatmap.iter().enumerate() {
+            assert_eq!(count, if script.contains(&index) { 4 } else { 0 });
+        }
+
+        assert_eq!(
+            eval::move_heatmap(&mut agent, Opponent::SelfPlay, 0).unwrap(),
+            vec![0; 15 * 15]
+        );
+    }
+
+    #[test]
+    fn test_train_loggers() {
+        #[derive(Debug)]
+        struct CapturingLogger {
+            epochs: Rc<RefCell<Vec<EpochReport>>>,
+            steps: Rc<RefCell<Vec<StepReport>>>,
+            recent_games: Rc<RefCell<Vec<usize>>>,
+        }
+
+        impl TrainLogger for CapturingLogger {
+            fn on_epoch(&mut self, report: &EpochReport) {
+                self.epochs.borrow_mut().push(report.clone());
+            }
+
+            fn on_step(&mut self, report: &StepReport) {
+                self.steps.borrow_mut().push(report.clone());
+            }
+
+            fn on_recent_game(&mut self, report: &RecentGameReport) {
+                assert_eq!(report.moves.len(), report.game.moves().len());
+                self.recent_games.borrow_mut().push(report.epoch);
+            }
+        }
+
+        let epochs = Rc::new(RefCell::new(vec![]));
+        let steps = Rc::new(RefCell::new(vec![]));
+        let recent_games = Rc::new(RefCell::new(vec![]));
+        let mut trainer = GomokuDDQNTrainer::new(vec![Box::new(CapturingLogger {
+            epochs: epochs.clone(),
+            steps: steps.clone(),
+            recent_games: recent_games.clone(),
+        })]);
+        let mut agent = GomokuDDQNAgent::with_device(
+            ModelConfig {
+                board_size: 15,
+                residual_blocks: 1,
+                residual_block_channels: 8.into(),
+                stages: None,
+                fc0_channels: 8,
+                include_turn_plane: true,
+                include_legal_plane: false,
+                normalize_input: false,
+                norm: NormKind::BatchNorm,
+                encoding: EncodingMode::Stacked,
+            },
+            Device::Cpu,
+        );
+        let options = Figment::new().merge(Toml::string(
+            "iterations = 4\ntraining_steps = 2\nbatch_size = 4\neval_games = 1\nrecent_game_every = 2",
+        ));
+
+        trainer.train(&mut agent, 3, options).unwrap();
+
+        let epochs = epochs.borrow();
+        assert_eq!(
+            Vec::from_iter(epochs.iter().map(|report| report.epoch)),
+            vec![0, 1, 2]
+        );
+        assert!(epochs
+            .iter()
+            .all(|report| report.agent_wins + report.opponent_wins + report.draws == 1));
+
+        let steps = steps.borrow();
+        assert_eq!(steps.len(), 3 * 2);
+        assert_eq!(*recent_games.borrow(), vec![0, 2]);
+        assert_eq!(trainer.into_loggers().len(), 1);
     }
 }
@@ -0,0 +1,90 @@
+use crate::agent::{Agent, AgentError};
+use figment::Figment;
+use gomoku_core::game::Game;
+
+/// A deterministic agent that plays a fixed list of moves, for tests.
+///
+/// Once the script is exhausted, it plays the lowest-index legal move.
+#[derive(Debug, Clone)]
+pub struct ScriptedAgent {
+    script: Vec<usize>,
+    next: usize,
+}
+
+impl ScriptedAgent {
+    pub fn new(script: Vec<usize>) -> Self {
+        Self { script, next: 0 }
+    }
+}
+
+impl Agent for ScriptedAgent {
+    fn save(&self, _path: &str) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+        if let Some(&index) = self.script.get(self.next) {
+            self.next += 1;
+            return Ok(index);
+        }
+
+        game.board()
+            .legal_moves()
+            .first()
+            .copied()
+            .ok_or(AgentError::NoLegalMoves)
+    }
+
+    /// Starts the script over.
+    fn reset(&mut self) {
+        self.next = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gomoku_core::game::{GameResult, Turn};
+
+    #[test]
+    fn test_scripted_game() {
+        // black builds a row from (7, 7) while white plays above it and never blocks
+        let mut black = ScriptedAgent::new(vec![112, 113, 114, 115, 116]);
+        let mut white = ScriptedAgent::new(vec![97, 98, 99, 100]);
+        let mut game = Game::new(15, 5);
+
+        while game.game_result().is_none() {
+            let index = match game.turn() {
+                Turn::Black => black.next_move(&game).unwrap(),
+                Turn::White => white.next_move(&game).unwrap(),
+            };
+            game.place_stone(index).unwrap();
+        }
+
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+        assert_eq!(game.moves(), &[112, 97, 113, 98, 114, 99, 115, 100, 116]);
+    }
+
+    #[test]
+    fn test_scripted_fallback() {
+        let mut agent = ScriptedAgent::new(vec![112]);
+        let mut game = Game::new(15, 5);
+
+        assert_eq!(agent.next_move(&game).unwrap(), 112);
+        game.place_stone(112).unwrap();
+        game.place_stone(0).unwrap();
+
+        assert_eq!(agent.next_move(&game).unwrap(), 1);
+
+        agent.reset();
+        assert_eq!(agent.next_move(&Game::new(15, 5)).unwrap(), 112);
+    }
+}
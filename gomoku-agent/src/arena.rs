@@ -0,0 +1,132 @@
+use crate::agent::{Agent, AgentError};
+use gomoku_core::game::{Game, GameResult, Turn};
+
+/// The outcome of `compare_agents`, from the point of view of the first agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareReport {
+    pub a_wins: usize,
+    pub b_wins: usize,
+    pub draws: usize,
+    /// The number of games the first agent played as black.
+    pub a_black_games: usize,
+    /// The first agent's wins over all games.
+    pub a_win_rate: f64,
+    /// The 95% Wilson score interval of `a_win_rate`.
+    pub confidence_interval: (f64, f64),
+}
+
+/// Plays a game between two agents on a 15×15 board with a win length of 5, resetting both first.
+pub fn play_game(black: &mut dyn Agent, white: &mut dyn Agent) -> Result<Game, AgentError> {
+    let mut game = Game::new(15, 5);
+    black.reset();
+    white.reset();
+
+    while game.game_result().is_none() {
+        let action = match game.turn() {
+            Turn::Black => black.next_move(&game)?,
+            Turn::White => white.next_move(&game)?,
+        };
+        game.place_stone(action)?;
+    }
+
+    Ok(game)
+}
+
+/// Plays `games` games between two agents, alternating colors with `a` as black first, so neither
+/// benefits from moving first more often.
+pub fn compare_agents(
+    a: &mut dyn Agent,
+    b: &mut dyn Agent,
+    games: usize,
+) -> Result<CompareReport, AgentError> {
+    let mut a_wins = 0;
+    let mut b_wins = 0;
+    let mut draws = 0;
+    let mut a_black_games = 0;
+
+    for index in 0..games {
+        let a_turn = if index % 2 == 0 {
+            Turn::Black
+        } else {
+            Turn::White
+        };
+        let game = match a_turn {
+            Turn::Black => {
+                a_black_games += 1;
+                play_game(a, b)?
+            }
+            Turn::White => play_game(b, a)?,
+        };
+
+        match game.game_result() {
+            Some(GameResult::Win(winner)) if winner == a_turn => a_wins += 1,
+            Some(GameResult::Win(_)) => b_wins += 1,
+            _ => draws += 1,
+        }
+    }
+
+    let a_win_rate = if games == 0 {
+        0.0
+    } else {
+        a_wins as f64 / games as f64
+    };
+
+    Ok(CompareReport {
+        a_wins,
+        b_wins,
+        draws,
+        a_black_games,
+        a_win_rate,
+        confidence_interval: wilson_interval(a_wins, games, 1.96),
+    })
+}
+
+/// Computes the Wilson score interval of a proportion of `successes` out of `trials`, for the
+/// normal quantile `z` (`1.96` for 95%). Returns `(0.0, 1.0)` without trials.
+pub fn wilson_interval(successes: usize, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::scripted::ScriptedAgent;
+
+    #[test]
+    fn test_compare_agents() {
+        // five in a row through the center, against stones scattered down the first column
+        let mut strong = ScriptedAgent::new(vec![112, 113, 114, 115, 116]);
+        let mut weak = ScriptedAgent::new(vec![0, 30, 60, 90, 120, 150, 180]);
+
+        let report = compare_agents(&mut strong, &mut weak, 10).unwrap();
+
+        assert_eq!(report.a_black_games, 5);
+        assert_eq!((report.a_wins, report.b_wins, report.draws), (10, 0, 0));
+        assert!(0.5 < report.a_win_rate);
+        assert!(0.5 < report.confidence_interval.0);
+        assert_eq!(report.confidence_interval.1, 1.0);
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        assert_eq!(wilson_interval(0, 0, 1.96), (0.0, 1.0));
+
+        let (lower, upper) = wilson_interval(50, 100, 1.96);
+        assert!((lower - 0.4038).abs() < 1e-4);
+        assert!((upper - 0.5962).abs() < 1e-4);
+
+        let (lower, upper) = wilson_interval(0, 10, 1.96);
+        assert_eq!(lower, 0.0);
+        assert!(0.0 < upper && upper < 0.5);
+    }
+}
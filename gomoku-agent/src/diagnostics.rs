@@ -0,0 +1,128 @@
+use crate::agent::{Agent, AgentError};
+use gomoku_core::{board::Symmetry, game::Game};
+
+/// Measures how consistently the agent plays across the 8 symmetries of a position.
+///
+/// The agent picks a move in every transformed copy of the game, and each move is mapped back to
+/// the original frame. Returns the fraction of those moves that agree with the move picked in the
+/// untransformed game; a well-trained agent should score close to `1.0`.
+///
+/// Each copy starts from the transformed starting board, so stones of a game built with
+/// `Game::from_board` or `Game::with_handicap` are kept, and replays the transformed moves under
+/// the same move limit.
+pub fn symmetry_consistency(agent: &mut dyn Agent, game: &Game) -> Result<f64, AgentError> {
+    let board_size = game.board_size();
+    let (start_turn, start_board) = &game.history()[0];
+    let mut moves = Vec::with_capacity(Symmetry::ALL.len());
+
+    for symmetry in Symmetry::ALL {
+        let mut transformed = Game::from_board(
+            start_board.apply_symmetry(symmetry),
+            *start_turn,
+            game.max_consecutive_stones(),
+        );
+        transformed.set_move_limit(game.move_limit());
+
+        for &index in game.moves() {
+            transformed
+                .place_stone(symmetry.map_index(index, board_size))
+                .expect("symmetric moves are legal");
+        }
+
+        let index = agent.next_move(&transformed)?;
+        moves.push(symmetry.inverse().map_index(index, board_size));
+    }
+
+    let identity = moves[0];
+    let agreeing = moves.iter().filter(|&&index| index == identity).count();
+
+    Ok(agreeing as f64 / moves.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::first_move::FirstMoveAgent;
+    use figment::Figment;
+    use gomoku_core::game::Turn;
+
+    /// Answers each move with its reflection through the center, which commutes with every
+    /// symmetry of the board.
+    struct MirrorAgent;
+
+    impl Agent for MirrorAgent {
+        fn save(&self, _path: &str) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+            let cells = game.board_size() * game.board_size();
+            Ok(cells - 1 - game.moves().last().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_symmetric_agent() {
+        let game = Game::from_moves(15, 5, &[16]).unwrap();
+
+        assert_eq!(symmetry_consistency(&mut MirrorAgent, &game).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_handicap_game() {
+        /// Plays the lowest empty cell, remembering what it was shown.
+        struct RecordingAgent {
+            seen: Vec<(usize, Turn, Option<usize>)>,
+        }
+
+        impl Agent for RecordingAgent {
+            fn save(&self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn load(&mut self, _path: &str) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn train(&mut self, _epoch: usize, _options: Figment) -> Result<(), AgentError> {
+                Ok(())
+            }
+
+            fn next_move(&mut self, game: &Game) -> Result<usize, AgentError> {
+                let stones = game.board().illegal_moves().len();
+                self.seen.push((stones, game.turn(), game.move_limit()));
+                Ok(game.board().legal_moves()[0])
+            }
+        }
+
+        let mut game = Game::with_handicap(15, 5, &[(Turn::Black, 0), (Turn::Black, 16)]).unwrap();
+        game.set_move_limit(Some(100));
+        game.place_stone(112).unwrap();
+        let mut agent = RecordingAgent { seen: vec![] };
+
+        symmetry_consistency(&mut agent, &game).unwrap();
+
+        assert_eq!(agent.seen, vec![(3, Turn::Black, Some(100)); 8]);
+        assert_eq!(symmetry_consistency(&mut MirrorAgent, &game).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_asymmetric_agent() {
+        // the lowest-index corner only maps back to itself under the identity and the main
+        // diagonal reflection
+        let game = Game::new(15, 5);
+
+        assert_eq!(
+            symmetry_consistency(&mut FirstMoveAgent, &game).unwrap(),
+            0.25
+        );
+    }
+}
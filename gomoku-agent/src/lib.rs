@@ -1,5 +1,9 @@
 pub mod agent;
 pub mod agent_provider;
 pub mod agents;
+pub mod arena;
+pub mod diagnostics;
 pub mod nn_utils;
+pub mod probe;
 pub mod replay;
+pub mod summary;
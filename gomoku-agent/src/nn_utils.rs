@@ -1,18 +1,84 @@
+use serde::{Deserialize, Serialize};
 use std::{borrow::Borrow, ops::Add};
 use tch::{
-    nn::{batch_norm2d, conv2d, BatchNorm, Conv2D, ConvConfig, Linear, ModuleT, Path},
+    nn::{
+        batch_norm2d, conv2d, group_norm, BatchNorm, Conv2D, ConvConfig, GroupNorm, Linear,
+        ModuleT, Path,
+    },
     no_grad, Tensor,
 };
 
+/// The normalization following each conv.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NormKind {
+    #[default]
+    BatchNorm,
+    /// Normalizes each sample over groups of channels, so it behaves the same in training and
+    /// evaluation and doesn't depend on the batch. `groups` must divide the channels.
+    GroupNorm {
+        groups: usize,
+    },
+    None,
+}
+
+/// A normalization layer of the given `NormKind`.
+#[derive(Debug)]
+pub enum Norm {
+    Batch(BatchNorm),
+    Group(GroupNorm),
+    Identity,
+}
+
+pub fn norm2d<'a>(vs: impl Borrow<Path<'a>>, kind: NormKind, channels: i64) -> Norm {
+    match kind {
+        NormKind::BatchNorm => Norm::Batch(batch_norm2d(vs, channels, Default::default())),
+        NormKind::GroupNorm { groups } => {
+            assert!(
+                groups != 0 && channels % groups as i64 == 0,
+                "{} groups don't divide {} channels",
+                groups,
+                channels
+            );
+            Norm::Group(group_norm(vs, groups as i64, channels, Default::default()))
+        }
+        NormKind::None => Norm::Identity,
+    }
+}
+
+impl Norm {
+    /// Returns the layer if it is a batch norm.
+    pub fn batch_norm(&self) -> Option<&BatchNorm> {
+        match self {
+            Norm::Batch(bn) => Some(bn),
+            _ => None,
+        }
+    }
+}
+
+impl ModuleT for Norm {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        match self {
+            Norm::Batch(bn) => xs.apply_t(bn, train),
+            Norm::Group(gn) => xs.apply(gn),
+            Norm::Identity => xs.shallow_clone(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ResidualBlock {
     pub conv1: Conv2D,
-    pub bn1: BatchNorm,
+    pub norm1: Norm,
     pub conv2: Conv2D,
-    pub bn2: BatchNorm,
+    pub norm2: Norm,
 }
 
-pub fn residual_block<'a>(vs: impl Borrow<Path<'a>>, channels: i64) -> ResidualBlock {
+pub fn residual_block<'a>(
+    vs: impl Borrow<Path<'a>>,
+    channels: i64,
+    norm: NormKind,
+) -> ResidualBlock {
     let vs = vs.borrow();
     let conv1 = conv2d(
         vs,
@@ -24,7 +90,7 @@ pub fn residual_block<'a>(vs: impl Borrow<Path<'a>>, channels: i64) -> ResidualB
             ..Default::default()
         },
     );
-    let bn1 = batch_norm2d(vs, channels, Default::default());
+    let norm1 = norm2d(vs, norm, channels);
     let conv2 = conv2d(
         vs,
         channels,
@@ -35,23 +101,23 @@ pub fn residual_block<'a>(vs: impl Borrow<Path<'a>>, channels: i64) -> ResidualB
             ..Default::default()
         },
     );
-    let bn2 = batch_norm2d(vs, channels, Default::default());
+    let norm2 = norm2d(vs, norm, channels);
 
     ResidualBlock {
         conv1,
-        bn1,
+        norm1,
         conv2,
-        bn2,
+        norm2,
     }
 }
 
 impl ModuleT for ResidualBlock {
     fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
         xs.apply(&self.conv1)
-            .apply_t(&self.bn1, train)
+            .apply_t(&self.norm1, train)
             .relu()
             .apply(&self.conv2)
-            .apply_t(&self.bn2, train)
+            .apply_t(&self.norm2, train)
             .add(xs)
             .relu()
     }
@@ -88,11 +154,52 @@ pub fn copy_weights_batch_norm2d(to: &mut BatchNorm, from: &BatchNorm, weight: f
     })
 }
 
+/// Copies the running mean and variance of a batch norm as is, leaving its weights untouched.
+pub fn copy_stats_batch_norm2d(to: &mut BatchNorm, from: &BatchNorm) {
+    no_grad(|| {
+        to.running_mean.copy_(&from.running_mean);
+        to.running_var.copy_(&from.running_var);
+    })
+}
+
+pub fn copy_weights_group_norm(to: &mut GroupNorm, from: &GroupNorm, weight: f64) {
+    no_grad(|| {
+        if let (Some(ws), Some(from_ws)) = (&mut to.ws, &from.ws) {
+            ws.copy_(&blend_weights(ws, from_ws, weight));
+        }
+
+        if let (Some(bs), Some(from_bs)) = (&mut to.bs, &from.bs) {
+            bs.copy_(&blend_weights(bs, from_bs, weight));
+        }
+    })
+}
+
+/// Copies the weights of a norm of the same kind; norms of differing kinds are left untouched.
+pub fn copy_weights_norm(to: &mut Norm, from: &Norm, weight: f64) {
+    match (to, from) {
+        (Norm::Batch(to), Norm::Batch(from)) => copy_weights_batch_norm2d(to, from, weight),
+        (Norm::Group(to), Norm::Group(from)) => copy_weights_group_norm(to, from, weight),
+        _ => {}
+    }
+}
+
+/// Copies the running statistics of a batch norm; other norms keep none.
+pub fn copy_stats_norm(to: &mut Norm, from: &Norm) {
+    if let (Norm::Batch(to), Norm::Batch(from)) = (to, from) {
+        copy_stats_batch_norm2d(to, from);
+    }
+}
+
 pub fn copy_weights_residual_block(to: &mut ResidualBlock, from: &ResidualBlock, weight: f64) {
     copy_weights_conv2d(&mut to.conv1, &from.conv1, weight);
-    copy_weights_batch_norm2d(&mut to.bn1, &from.bn1, weight);
+    copy_weights_norm(&mut to.norm1, &from.norm1, weight);
     copy_weights_conv2d(&mut to.conv2, &from.conv2, weight);
-    copy_weights_batch_norm2d(&mut to.bn2, &from.bn2, weight);
+    copy_weights_norm(&mut to.norm2, &from.norm2, weight);
+}
+
+pub fn copy_stats_residual_block(to: &mut ResidualBlock, from: &ResidualBlock) {
+    copy_stats_norm(&mut to.norm1, &from.norm1);
+    copy_stats_norm(&mut to.norm2, &from.norm2);
 }
 
 pub fn copy_weights_linear(to: &mut Linear, from: &Linear, weight: f64) {
@@ -0,0 +1,144 @@
+use crate::agent::{Agent, AgentError};
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use gomoku_core::game::{FromMovesError, Game};
+use serde::Deserialize;
+
+/// A held-out set of positions with known best moves, e.g. forced wins, to track how often an
+/// agent finds them. Unlike the win-rate against an opponent, the result doesn't depend on luck.
+///
+/// Probe sets are read from TOML files with an optional `board_size` and `win_len`, 15 and 5 by
+/// default, and a `[[positions]]` table per position. Each position has the `moves` leading to it
+/// from the empty board and the `best` moves, any of which counts as correct.
+#[derive(Debug, Clone)]
+pub struct ProbeSet {
+    positions: Vec<(Game, Vec<usize>)>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFile {
+    #[serde(default = "default_board_size")]
+    board_size: usize,
+    #[serde(default = "default_win_len")]
+    win_len: usize,
+    positions: Vec<ProbePosition>,
+}
+
+#[derive(Deserialize)]
+struct ProbePosition {
+    moves: Vec<usize>,
+    best: Vec<usize>,
+}
+
+fn default_board_size() -> usize {
+    15
+}
+
+fn default_win_len() -> usize {
+    5
+}
+
+impl ProbeSet {
+    /// Reads a probe set from a TOML file, replaying the moves of every position.
+    pub fn load(path: &str) -> Result<Self, AgentError> {
+        Self::from_figment(Figment::new().merge(Toml::file_exact(path)))
+    }
+
+    fn from_figment(figment: Figment) -> Result<Self, AgentError> {
+        let file: ProbeFile = figment.extract()?;
+        Game::try_new(file.board_size, file.win_len)
+            .map_err(|err| figment::Error::from(err.to_string()))?;
+
+        let positions = file
+            .positions
+            .into_iter()
+            .map(|position| {
+                let game = Game::from_moves(file.board_size, file.win_len, &position.moves)
+                    .map_err(|err| match err {
+                        FromMovesError::InvalidConfig(err) => {
+                            AgentError::from(figment::Error::from(err.to_string()))
+                        }
+                        FromMovesError::InvalidMove { source, .. } => source.into(),
+                    })?;
+                Ok((game, position.best))
+            })
+            .collect::<Result<_, AgentError>>()?;
+
+        Ok(Self { positions })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The fraction of positions in which the agent picks one of the best moves, or zero for an
+    /// empty set.
+    pub fn accuracy(&self, agent: &mut dyn Agent) -> Result<f64, AgentError> {
+        if self.positions.is_empty() {
+            return Ok(0.0);
+        }
+
+        let mut correct = 0;
+
+        for (game, best) in &self.positions {
+            if best.contains(&agent.next_move(game)?) {
+                correct += 1;
+            }
+        }
+
+        Ok(correct as f64 / self.positions.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::scripted::ScriptedAgent;
+
+    fn parse(toml: &str) -> Result<ProbeSet, AgentError> {
+        ProbeSet::from_figment(Figment::new().merge(Toml::string(toml)))
+    }
+
+    #[test]
+    fn test_accuracy() {
+        // the agent plays the lowest empty cell, which wins only in the first position, and is
+        // also the best opening move in the last
+        let probe_set = parse(
+            "[[positions]]\nmoves = [1, 20, 2, 21, 3, 22, 4, 23]\nbest = [0, 5]\n\
+             [[positions]]\nmoves = [112, 1, 113, 2, 114, 3, 115, 4]\nbest = [111, 116]\n\
+             [[positions]]\nmoves = [0, 20, 16, 21, 32, 22, 48, 23]\nbest = [64]\n\
+             [[positions]]\nmoves = []\nbest = [0]",
+        )
+        .unwrap();
+        assert_eq!(probe_set.len(), 4);
+
+        let mut agent = ScriptedAgent::new(vec![]);
+        assert_eq!(probe_set.accuracy(&mut agent).unwrap(), 0.5);
+
+        let empty = parse("positions = []").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.accuracy(&mut agent).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_invalid_probe_set() {
+        assert!(matches!(
+            parse("[[positions]]\nmoves = [112, 112]\nbest = [0]"),
+            Err(AgentError::IllegalMove(_))
+        ));
+        assert!(matches!(
+            parse("win_len = 16\npositions = []"),
+            Err(AgentError::Config(_))
+        ));
+        assert!(matches!(
+            ProbeSet::load("this/path/does/not/exist.toml"),
+            Err(AgentError::Config(_))
+        ));
+    }
+}
@@ -1,9 +1,11 @@
 use crate::agent::Agent;
 use gomoku_core::{
-    board::Board,
+    board::{Board, Cell},
     game::{Game, GameResult, PlaceStoneResult, Turn},
 };
-use rand::{seq::SliceRandom, Rng};
+use rand::{distributions::WeightedError, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
 
 #[derive(Debug, Clone)]
 pub struct ReplayStep {
@@ -13,115 +15,486 @@ pub struct ReplayStep {
     pub next_boards: Option<[(Turn, Board); 4]>,
     pub game_result: Option<GameResult>,
     pub reward: f32,
+    /// Whether the step comes from an expert demonstration rather than the agent's own play.
+    pub is_demonstration: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+impl ReplayStep {
+    /// Rebuilds a game at the step's most recent frame, for inspecting the position.
+    ///
+    /// The frames hold boards rather than moves, so the game starts from the board directly and
+    /// carries no move history.
+    pub fn to_game(&self, board_size: usize, win_len: usize) -> Game {
+        let (turn, board) = self.boards.last().unwrap();
+        debug_assert_eq!(board.board_size(), board_size);
+
+        Game::from_board(board.clone(), *turn, win_len)
+    }
+}
+
+/// How a full replay buffer makes room for a new step.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Evicts the oldest step.
+    #[default]
+    Fifo,
+    /// Keeps a uniform sample of every step pushed so far (reservoir sampling). A new step replaces a
+    /// random stored one, or is dropped.
+    Reservoir,
+    /// Evicts the stored step with the lowest priority, the oldest one among ties.
+    LowestPriority,
+}
+
+/// A replay buffer that evicts steps according to its `EvictionPolicy` once full.
+///
+/// Demonstration steps are kept apart from the regular steps: they don't count towards the
+/// capacity and are never evicted.
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    capacity: usize,
+    eviction: EvictionPolicy,
+    steps: VecDeque<ReplayStep>,
+    /// Priority of each regular step, in the same order as `steps`.
+    priorities: VecDeque<f64>,
+    /// Number of regular steps pushed so far, including evicted and dropped ones.
+    pushed: usize,
+    demonstrations: Vec<ReplayStep>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_eviction(capacity, EvictionPolicy::Fifo)
+    }
+
+    pub fn with_eviction(capacity: usize, eviction: EvictionPolicy) -> Self {
+        Self {
+            capacity,
+            eviction,
+            steps: VecDeque::with_capacity(capacity),
+            priorities: VecDeque::with_capacity(capacity),
+            pushed: 0,
+            demonstrations: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len() + self.demonstrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.demonstrations.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ReplayStep> {
+        self.demonstrations.iter().chain(self.steps.iter())
+    }
+
+    pub fn push(&mut self, step: ReplayStep) {
+        self.push_with_priority(step, 0.0);
+    }
+
+    /// Pushes a step with the given priority, which only matters for
+    /// `EvictionPolicy::LowestPriority`.
+    pub fn push_with_priority(&mut self, step: ReplayStep, priority: f64) {
+        if step.is_demonstration {
+            self.demonstrations.push(step);
+            return;
+        }
+
+        self.pushed += 1;
+
+        if self.steps.is_empty() || self.steps.len() < self.capacity {
+            self.steps.push_back(step);
+            self.priorities.push_back(priority);
+            return;
+        }
+
+        match self.eviction {
+            EvictionPolicy::Fifo => {
+                self.steps.pop_front();
+                self.priorities.pop_front();
+            }
+            EvictionPolicy::Reservoir => {
+                let index = rand::thread_rng().gen_range(0..self.pushed);
+
+                if index < self.steps.len() {
+                    self.steps[index] = step;
+                    self.priorities[index] = priority;
+                }
+
+                return;
+            }
+            EvictionPolicy::LowestPriority => {
+                let (index, _) = self
+                    .priorities
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, lhs), (_, rhs)| f64::total_cmp(lhs, rhs))
+                    .unwrap();
+                self.steps.remove(index);
+                self.priorities.remove(index);
+            }
+        }
+
+        self.steps.push_back(step);
+        self.priorities.push_back(priority);
+    }
+
+    /// Samples up to `batch_size` distinct steps.
+    ///
+    /// Demonstration steps are `demonstration_weight` times as likely to be picked as regular steps,
+    /// and steps ending a game `terminal_oversample` times as likely as the others. Both factors
+    /// apply to a terminal demonstration step. Fails if a weight is negative or not a number.
+    pub fn sample(
+        &self,
+        rng: &mut impl Rng,
+        batch_size: usize,
+        demonstration_weight: f64,
+        terminal_oversample: f64,
+    ) -> Result<Vec<&ReplayStep>, WeightedError> {
+        let steps = self.iter().collect::<Vec<_>>();
+
+        steps
+            .choose_multiple_weighted(rng, batch_size, |step| {
+                let weight = if step.is_demonstration {
+                    demonstration_weight
+                } else {
+                    1.0
+                };
+
+                if step.game_result.is_some() {
+                    weight * terminal_oversample
+                } else {
+                    weight
+                }
+            })
+            .map(|steps| steps.copied().collect())
+    }
+}
+
+/// Shaped rewards given for non-terminal moves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardConfig {
+    /// Reward for a move whose longest own line has the given length, indexed by length.
+    /// Lengths beyond the table earn nothing.
+    pub offensive: Vec<f32>,
+    /// Reward for a move that blocks an opponent line of 4 or 5 stones.
+    pub defensive: f32,
+    /// Subtracted from the reward of every non-terminal move, to discourage dragging games out.
+    pub step_penalty: f32,
+    /// Reward for choosing an occupied cell; see `ReplayConfig::penalize_illegal`.
+    pub illegal_move: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            offensive: vec![0f32, 0f32, 0f32, 0.01f32, 0.01f32, 0.01f32],
+            defensive: 0.01f32,
+            step_penalty: 0f32,
+            illegal_move: -1f32,
+        }
+    }
+}
+
+/// How the agent explores in a `ReplayStream`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exploration {
+    /// Probability of playing a random move instead of the agent's move.
+    pub epsilon: f64,
+    /// Whether random moves are restricted to cells next to existing stones (the center on an empty
+    /// board) instead of all legal moves.
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Opponent {
     Random,
+    /// The rule-based player that also plays the demonstration games.
+    Heuristic,
     SelfPlay,
 }
 
-pub fn sample_replay(
+/// Picks the opponent's move in the given game.
+pub fn opponent_move<A: ?Sized + Agent>(opponent: Opponent, agent: &mut A, game: &Game) -> usize {
+    match opponent {
+        Opponent::Random => RandomPlayer::new().generate_move(game),
+        Opponent::Heuristic => TacticalPlayer::new().generate_move(game),
+        Opponent::SelfPlay => agent.generate_move(game),
+    }
+}
+
+/// Settings of a `ReplayStream`. The fields may be changed between steps, e.g. to decay epsilon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    pub board_size: usize,
+    pub win_len: usize,
+    pub exploration: Exploration,
+    pub reward: RewardConfig,
+    /// Probability of the agent playing black in each new game.
+    pub black_probability: f64,
+    /// Whether the agent choosing an occupied cell yields a step rewarded with
+    /// `reward.illegal_move` that leaves the game as it was, instead of a panic. The agent then
+    /// plays a random legal move in the next step.
+    pub penalize_illegal: bool,
+}
+
+/// An endless stream of the agent's replay steps against an opponent.
+///
+/// Each step is one move of the agent followed by the opponent's reply. When the opponent wins, the
+/// step is the opponent's winning move instead. A new game starts, with the agent's color drawn
+/// from `black_probability`, once the current one is finished.
+pub struct ReplayStream<'a, A: ?Sized + Agent> {
+    agent: &'a mut A,
+    opponent: Opponent,
+    config: ReplayConfig,
     game: Game,
     agent_turn: Turn,
-    agent: &mut dyn Agent,
-    opponent: Opponent,
-    epsilon: f64,
-) -> (Game, Turn, ReplayStep) {
-    let mut rng = rand::thread_rng();
-    let mut game = game;
-    let mut agent_turn = agent_turn;
-
-    // start a new game if the current game is finished
-    if game.game_result().is_some() {
-        let new_game = Game::new(game.board_size(), game.max_consecutive_stones());
-        let new_agent_turn = if rng.gen_bool(0.5) {
-            Turn::Black
-        } else {
-            Turn::White
-        };
+    /// Whether the agent's last move was illegal, so that its next one is picked at random.
+    reselect: bool,
+}
 
-        game = new_game;
-        agent_turn = new_agent_turn;
+impl<'a, A: ?Sized + Agent> ReplayStream<'a, A> {
+    pub fn new(agent: &'a mut A, opponent: Opponent, config: ReplayConfig) -> Self {
+        let game = Game::new(config.board_size, config.win_len);
+        let agent_turn = random_turn(config.black_probability);
+
+        Self {
+            agent,
+            opponent,
+            config,
+            game,
+            agent_turn,
+            reselect: false,
+        }
     }
 
-    // let opponent play if it's not the agent's turn
-    // NOTE: there is no case where the opponent wins the game at this point
-    if game.turn() != agent_turn {
-        let action = match opponent {
-            Opponent::Random => RandomPlayer::new().generate_move(&game),
-            Opponent::SelfPlay => agent.generate_move(&game),
-        };
-        game.place_stone(action).unwrap();
+    pub fn agent(&self) -> &A {
+        self.agent
     }
 
-    // let agent play
-    let boards = generate_history_boards(agent_turn, &game);
-    let agent_action = if 1e-4 < epsilon && rng.gen_bool(epsilon) {
-        let legal_moves = game.board().legal_moves();
-        *legal_moves.choose(&mut rng).unwrap()
-    } else {
-        agent.generate_move(&game)
-    };
-    let result_after_agent = game.place_stone(agent_action).unwrap();
+    pub fn agent_mut(&mut self) -> &mut A {
+        self.agent
+    }
 
-    // return immediately if the game is finished (agent wins)
-    if result_after_agent.game_result.is_some() {
-        return (
-            game,
-            agent_turn,
-            ReplayStep {
+    pub fn opponent(&self) -> Opponent {
+        self.opponent
+    }
+
+    pub fn set_opponent(&mut self, opponent: Opponent) {
+        self.opponent = opponent;
+    }
+
+    pub fn config(&self) -> &ReplayConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut ReplayConfig {
+        &mut self.config
+    }
+
+    /// The game being played. It stays finished after the last step of a game until the next step.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The agent's color in the current game.
+    pub fn agent_turn(&self) -> Turn {
+        self.agent_turn
+    }
+}
+
+impl<A: ?Sized + Agent> Iterator for ReplayStream<'_, A> {
+    type Item = ReplayStep;
+
+    fn next(&mut self) -> Option<ReplayStep> {
+        let mut rng = rand::thread_rng();
+
+        // start a new game if the current game is finished
+        if self.game.game_result().is_some() {
+            self.game = Game::new(self.config.board_size, self.config.win_len);
+            self.agent_turn = random_turn(self.config.black_probability);
+        }
+
+        let game = &mut self.game;
+        let agent_turn = self.agent_turn;
+
+        // let opponent play if it's not the agent's turn
+        // NOTE: there is no case where the opponent wins the game at this point
+        if game.turn() != agent_turn {
+            let action = opponent_move(self.opponent, self.agent, game);
+            game.place_stone(action).unwrap();
+        }
+
+        // let agent play
+        let exploration = self.config.exploration;
+        let boards = generate_history_boards(agent_turn, game);
+        let explore = 1e-4 < exploration.epsilon && rng.gen_bool(exploration.epsilon);
+        let agent_action = if std::mem::take(&mut self.reselect) || explore {
+            let moves = if exploration.focused {
+                game.board().candidate_moves(1)
+            } else {
+                game.board().legal_moves()
+            };
+            *moves.choose(&mut rng).unwrap()
+        } else {
+            self.agent.generate_move(game)
+        };
+
+        if self.config.penalize_illegal && game.board().get_cell(agent_action) != Some(Cell::Empty)
+        {
+            self.reselect = true;
+
+            return Some(ReplayStep {
+                turn: agent_turn,
+                action: agent_action,
+                next_boards: Some(boards.clone()),
+                boards,
+                game_result: None,
+                reward: self.config.reward.illegal_move,
+                is_demonstration: false,
+            });
+        }
+
+        let result_after_agent = game.place_stone(agent_action).unwrap();
+
+        // return immediately if the game is finished (agent wins)
+        if result_after_agent.game_result.is_some() {
+            return Some(ReplayStep {
                 turn: result_after_agent.turn_was,
                 action: agent_action,
                 boards,
                 next_boards: None,
                 game_result: result_after_agent.game_result,
                 reward: 10f32,
-            },
-        );
-    }
+                is_demonstration: false,
+            });
+        }
 
-    // let opponent play
-    let opponent_action = match opponent {
-        Opponent::Random => RandomPlayer::new().generate_move(&game),
-        Opponent::SelfPlay => agent.generate_move(&game),
-    };
-    let result_after_opponent = game.place_stone(opponent_action).unwrap();
+        // let opponent play
+        let opponent_action = opponent_move(self.opponent, self.agent, game);
+        let result_after_opponent = game.place_stone(opponent_action).unwrap();
 
-    // return immediately if the game is finished (opponent wins)
-    if result_after_opponent.game_result.is_some() {
-        return (
-            game,
-            agent_turn,
-            ReplayStep {
+        // return immediately if the game is finished (opponent wins)
+        if result_after_opponent.game_result.is_some() {
+            return Some(ReplayStep {
                 turn: result_after_opponent.turn_was,
                 action: opponent_action,
                 boards,
                 next_boards: None,
                 game_result: result_after_opponent.game_result,
                 reward: -10f32,
-            },
-        );
-    }
+                is_demonstration: false,
+            });
+        }
 
-    // compute reward
-    let reward = compute_nonterminal_reward(&result_after_agent);
-    let next_boards = Some(generate_history_boards(game.turn(), &game));
+        // compute reward
+        let reward = compute_nonterminal_reward(&result_after_agent, &self.config.reward);
+        let next_boards = Some(generate_history_boards(game.turn(), game));
 
-    (
-        game,
-        agent_turn,
-        ReplayStep {
+        Some(ReplayStep {
             turn: result_after_agent.turn_was,
             action: agent_action,
             boards,
             next_boards,
             game_result: result_after_agent.game_result,
             reward,
-        },
-    )
+            is_demonstration: false,
+        })
+    }
+}
+
+fn random_turn(black_probability: f64) -> Turn {
+    if rand::thread_rng().gen_bool(black_probability) {
+        Turn::Black
+    } else {
+        Turn::White
+    }
+}
+
+/// Counts the distinct positions among the steps, treating symmetric and color-swapped positions
+/// as the same.
+///
+/// A low count relative to the number of steps means the play has collapsed to a narrow set of lines.
+pub fn count_unique_positions<'a>(steps: impl IntoIterator<Item = &'a ReplayStep>) -> usize {
+    steps
+        .into_iter()
+        .map(|step| step.boards.last().unwrap().1.canonical_hash())
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Generates demonstration steps from games the tactical player plays against itself.
+///
+/// Every move of both players is recorded from the mover's point of view, with the same rewards
+/// as a `ReplayStream`.
+pub fn generate_demonstrations(
+    board_size: usize,
+    max_consecutive_stones: usize,
+    games: usize,
+    reward_config: &RewardConfig,
+) -> Vec<ReplayStep> {
+    let mut player = TacticalPlayer::new();
+    let mut steps = vec![];
+
+    for _ in 0..games {
+        let mut played = Game::new(board_size, max_consecutive_stones);
+
+        while played.game_result().is_none() {
+            let action = player.generate_move(&played);
+            played.place_stone(action).unwrap();
+        }
+
+        let moves = played.moves();
+        let mut game = Game::new(board_size, max_consecutive_stones);
+
+        for (i, &action) in moves.iter().enumerate() {
+            let turn = game.turn();
+            let boards = generate_history_boards(turn, &game);
+            let result = game.place_stone(action).unwrap();
+
+            let (next_boards, game_result, reward) = if result.game_result.is_some() {
+                (None, result.game_result, 10f32)
+            } else {
+                // the game always ends with a result, so there is a reply to every non-final move
+                let mut next_game = game.clone();
+                let reply_result = next_game.place_stone(moves[i + 1]).unwrap();
+
+                if reply_result.game_result.is_some() {
+                    (None, reply_result.game_result, -10f32)
+                } else {
+                    (
+                        Some(generate_history_boards(turn, &next_game)),
+                        None,
+                        compute_nonterminal_reward(&result, reward_config),
+                    )
+                }
+            };
+
+            steps.push(ReplayStep {
+                turn,
+                action,
+                boards,
+                next_boards,
+                game_result,
+                reward,
+                is_demonstration: true,
+            });
+        }
+    }
+
+    steps
 }
 
+/// Collects the last 4 positions in which `player` was to move, ordered from oldest to newest.
+///
+/// When `player` is to move in `game`, the last frame is the current position. Early in the game,
+/// the missing frames are padded with empty boards at the front. The initial empty board counts as
+/// a position black was to move in, but never as one of white's, so both players see exactly one
+/// real frame per move they faced.
 pub fn generate_history_boards(player: Turn, game: &Game) -> [(Turn, Board); 4] {
     let mut boards = game
         .history()
@@ -131,6 +504,7 @@ pub fn generate_history_boards(player: Turn, game: &Game) -> [(Turn, Board); 4]
         .take(4)
         .map(|(_, board)| (player, board.clone()))
         .collect::<Vec<_>>();
+    boards.reverse();
 
     while boards.len() < 4 {
         boards.insert(0, (player, Board::new(game.board_size())));
@@ -172,25 +546,462 @@ impl Player for RandomPlayer {
     }
 }
 
-fn compute_nonterminal_reward(result: &PlaceStoneResult) -> f32 {
-    // +1 for 3-5 consecutive stones (offensive)
-    if let Some(n) = result.consecutive_stones.first().copied() {
-        if (3..=5).contains(&n) {
-            return 0.01f32;
+/// A simple rule-based player: wins if it can, blocks the opponent's win otherwise, and extends
+/// its longest line as a fallback.
+struct TacticalPlayer {
+    rng: rand::rngs::ThreadRng,
+}
+
+impl TacticalPlayer {
+    fn new() -> Self {
+        Self {
+            rng: rand::thread_rng(),
         }
     }
 
-    // +1 for defensive move (blocking opponent's 4-5 consecutive stones)
-    let mut virtual_board = result.board_was.clone();
-    virtual_board.set_cell(result.index, result.turn_was.next().into());
+    fn longest_line_if_placed(game: &Game, index: usize, turn: Turn) -> usize {
+        let mut board = game.board().clone();
+        board.set_cell(index, turn.into());
+        board
+            .count_consecutive_cells(index, turn)
+            .first()
+            .copied()
+            .unwrap_or(1)
+    }
+}
+
+impl Player for TacticalPlayer {
+    fn generate_move(&mut self, game: &Game) -> usize {
+        let legal_moves = game.board().legal_moves();
+        debug_assert!(!legal_moves.is_empty());
 
-    let opponent_consecutive_stones =
-        virtual_board.count_consecutive_cells(result.index, result.turn_was.next());
+        let turn = game.turn();
+        let win_len = game.max_consecutive_stones();
+
+        for player in [turn, turn.next()] {
+            if let Some(&index) = legal_moves
+                .iter()
+                .find(|&&index| Self::longest_line_if_placed(game, index, player) == win_len)
+            {
+                return index;
+            }
+        }
+
+        let lines = legal_moves
+            .iter()
+            .map(|&index| Self::longest_line_if_placed(game, index, turn))
+            .collect::<Vec<_>>();
+        let longest = lines.iter().copied().max().unwrap();
+        let best_moves = legal_moves
+            .iter()
+            .zip(lines)
+            .filter_map(|(&index, line)| if line == longest { Some(index) } else { None })
+            .collect::<Vec<_>>();
+
+        best_moves.choose(&mut self.rng).copied().unwrap()
+    }
+}
+
+fn compute_nonterminal_reward(result: &PlaceStoneResult, reward_config: &RewardConfig) -> f32 {
+    compute_shaped_reward(result, reward_config) - reward_config.step_penalty
+}
+
+fn compute_shaped_reward(result: &PlaceStoneResult, reward_config: &RewardConfig) -> f32 {
+    // offensive: reward scaled by the length of the longest line
+    if let Some(n) = result.consecutive_stones().first().copied() {
+        let reward = reward_config.offensive.get(n).copied().unwrap_or(0f32);
+
+        if reward != 0f32 {
+            return reward;
+        }
+    }
+
+    // defensive: blocking opponent's 4-5 consecutive stones, i.e. the lines the opponent would
+    // have made by playing there instead
+    let opponent_consecutive_stones = result
+        .board_was
+        .count_consecutive_cells_placing(result.index, result.turn_was.next());
     if let Some(n) = opponent_consecutive_stones.first().copied() {
         if (4..=5).contains(&n) {
-            return 0.01f32;
+            return reward_config.defensive;
         }
     }
 
     0f32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn config(epsilon: f64, focused: bool) -> ReplayConfig {
+        ReplayConfig {
+            board_size: 15,
+            win_len: 5,
+            exploration: Exploration { epsilon, focused },
+            reward: RewardConfig::default(),
+            black_probability: 0.5,
+            penalize_illegal: false,
+        }
+    }
+
+    fn step(action: usize, is_demonstration: bool) -> ReplayStep {
+        step_with_board(action, is_demonstration, Board::new(15))
+    }
+
+    fn step_with_board(action: usize, is_demonstration: bool, board: Board) -> ReplayStep {
+        let boards = std::array::from_fn(|_| (Turn::Black, board.clone()));
+
+        ReplayStep {
+            turn: Turn::Black,
+            action,
+            boards,
+            next_boards: None,
+            game_result: None,
+            reward: 0f32,
+            is_demonstration,
+        }
+    }
+
+    #[test]
+    fn test_focused_exploration() {
+        let mut agent = crate::agents::scripted::ScriptedAgent::new(vec![]);
+        let mut stream = ReplayStream::new(&mut agent, Opponent::Random, config(1.0, true));
+
+        for _ in 0..500 {
+            let step = stream.next().unwrap();
+
+            if step.turn != stream.agent_turn() {
+                continue;
+            }
+
+            let board = &step.boards.last().unwrap().1;
+
+            if board.cells().iter().all(|cell| cell.is_empty()) {
+                continue;
+            }
+
+            let (row, column) = board.to_coords(step.action).unwrap();
+            let is_adjacent = board.cells().iter().enumerate().any(|(index, cell)| {
+                let (y, x) = board.to_coords(index).unwrap();
+                !cell.is_empty() && row.abs_diff(y) <= 1 && column.abs_diff(x) <= 1
+            });
+            assert!(is_adjacent, "{} is not next to a stone", step.action);
+        }
+    }
+
+    #[test]
+    fn test_replay_stream() {
+        let mut agent = crate::agents::scripted::ScriptedAgent::new(vec![]);
+        let mut stream = ReplayStream::new(&mut agent, Opponent::Random, config(0.5, false));
+
+        let steps = Vec::from_iter(stream.by_ref().take(10));
+        assert_eq!(steps.len(), 10);
+
+        let step = steps.last().unwrap();
+        let board = stream.game().board();
+        assert!(step.boards.last().unwrap().1.cells()[step.action].is_empty());
+        assert!(!board.cells()[step.action].is_empty());
+        assert_eq!(step.game_result, stream.game().game_result());
+
+        if step.game_result.is_none() {
+            assert_eq!(step.turn, stream.agent_turn());
+            assert_eq!(stream.game().turn(), stream.agent_turn());
+            assert!(step.next_boards.is_some());
+        }
+
+        for step in &steps {
+            let board = &step.boards.last().unwrap().1;
+            assert!(board.cells()[step.action].is_empty());
+            assert_eq!(step.next_boards.is_none(), step.game_result.is_some());
+        }
+    }
+
+    #[test]
+    fn test_penalize_illegal() {
+        // the agent repeats its first move
+        let mut agent = crate::agents::scripted::ScriptedAgent::new(vec![112, 112]);
+        let config = ReplayConfig {
+            black_probability: 1.0,
+            penalize_illegal: true,
+            ..config(0.0, false)
+        };
+        let mut stream = ReplayStream::new(&mut agent, Opponent::Heuristic, config);
+
+        let first = stream.next().unwrap();
+        assert_eq!(first.action, 112);
+        let game = stream.game().clone();
+
+        let illegal = stream.next().unwrap();
+        assert_eq!(illegal.action, 112);
+        assert_eq!(illegal.reward, RewardConfig::default().illegal_move);
+        assert_eq!(illegal.game_result, None);
+        assert_eq!(illegal.next_boards.as_ref(), Some(&illegal.boards));
+        assert_eq!(stream.game().moves(), game.moves());
+
+        let next = stream.next().unwrap();
+        assert!(game.board().get_cell(next.action).unwrap().is_empty());
+        assert_eq!(stream.game().moves()[..game.moves().len()], *game.moves());
+        assert_eq!(stream.game().moves()[game.moves().len()], next.action);
+    }
+
+    #[test]
+    fn test_reservoir_eviction() {
+        let mut buffer = ReplayBuffer::with_eviction(100, EvictionPolicy::Reservoir);
+
+        for action in 0..10000 {
+            buffer.push(step(action, false));
+        }
+
+        assert_eq!(buffer.len(), 100);
+
+        // each quarter of the stream should hold about a quarter of the buffer
+        let mut quarters = [0; 4];
+        for step in buffer.iter() {
+            quarters[step.action / 2500] += 1;
+        }
+
+        for count in quarters {
+            assert!((5..=45).contains(&count), "{:?}", quarters);
+        }
+    }
+
+    #[test]
+    fn test_lowest_priority_eviction() {
+        let mut buffer = ReplayBuffer::with_eviction(3, EvictionPolicy::LowestPriority);
+        buffer.push_with_priority(step(0, false), 2.0);
+        buffer.push_with_priority(step(1, false), 0.5);
+        buffer.push_with_priority(step(2, false), 1.0);
+        buffer.push_with_priority(step(3, false), 3.0);
+
+        let actions = buffer.iter().map(|step| step.action).collect::<Vec<_>>();
+        assert_eq!(actions, vec![0, 2, 3]);
+
+        // ties evict the oldest step
+        buffer.push_with_priority(step(4, false), 1.0);
+        buffer.push_with_priority(step(5, false), 1.0);
+
+        let actions = buffer.iter().map(|step| step.action).collect::<Vec<_>>();
+        assert_eq!(actions, vec![0, 3, 5]);
+    }
+
+    #[test]
+    fn test_history_boards_opening() {
+        let empty = Board::new(15);
+        let game = Game::new(15, 5);
+
+        // black faces only the initial empty board, padded with empties
+        let boards = generate_history_boards(Turn::Black, &game);
+        assert!(boards
+            .iter()
+            .all(|(turn, board)| *turn == Turn::Black && *board == empty));
+    }
+
+    #[test]
+    fn test_history_boards_after_one_move() {
+        let empty = Board::new(15);
+        let mut game = Game::new(15, 5);
+        game.place_stone(112).unwrap();
+
+        // white has faced a single position, which is the current one
+        let boards = generate_history_boards(Turn::White, &game);
+        let expected = [
+            (Turn::White, empty.clone()),
+            (Turn::White, empty.clone()),
+            (Turn::White, empty.clone()),
+            (Turn::White, game.board().clone()),
+        ];
+        assert_eq!(boards, expected);
+    }
+
+    #[test]
+    fn test_history_boards_after_five_moves() {
+        let empty = Board::new(15);
+        let mut game = Game::new(15, 5);
+        let mut positions = vec![game.board().clone()];
+
+        for index in [112, 113, 127, 128, 142] {
+            game.place_stone(index).unwrap();
+            positions.push(game.board().clone());
+        }
+
+        // white faced the positions after moves 1, 3 and 5, oldest first
+        let boards = generate_history_boards(Turn::White, &game);
+        let expected = [
+            (Turn::White, empty.clone()),
+            (Turn::White, positions[1].clone()),
+            (Turn::White, positions[3].clone()),
+            (Turn::White, positions[5].clone()),
+        ];
+        assert_eq!(boards, expected);
+
+        // black faced the initial position and the positions after moves 2 and 4
+        let boards = generate_history_boards(Turn::Black, &game);
+        let expected = [
+            (Turn::Black, empty.clone()),
+            (Turn::Black, positions[0].clone()),
+            (Turn::Black, positions[2].clone()),
+            (Turn::Black, positions[4].clone()),
+        ];
+        assert_eq!(boards, expected);
+    }
+
+    #[test]
+    fn test_demonstrations_are_never_evicted() {
+        let mut buffer = ReplayBuffer::new(4);
+
+        buffer.push(step(0, true));
+        buffer.push(step(1, true));
+
+        for action in 2..10 {
+            buffer.push(step(action, false));
+        }
+
+        let actions = buffer.iter().map(|step| step.action).collect::<Vec<_>>();
+        assert_eq!(actions, vec![0, 1, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_terminal_oversample() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut buffer = ReplayBuffer::new(1000);
+
+        // 1% of the steps end a game
+        for action in 0..1000 {
+            let mut step = step(action % 225, false);
+            if action % 100 == 0 {
+                step.game_result = Some(GameResult::Win(Turn::Black));
+            }
+            buffer.push(step);
+        }
+
+        let terminal_fraction = |rng: &mut StdRng, terminal_oversample| {
+            let mut terminal = 0;
+
+            for _ in 0..200 {
+                terminal += buffer
+                    .sample(rng, 32, 1.0, terminal_oversample)
+                    .unwrap()
+                    .into_iter()
+                    .filter(|step| step.game_result.is_some())
+                    .count();
+            }
+
+            terminal as f64 / (200 * 32) as f64
+        };
+
+        assert!(terminal_fraction(&mut rng, 1.0) < 0.03);
+        // 10 steps of weight 50 against 990 of weight 1 make up about a third of the batches
+        assert!(0.2 < terminal_fraction(&mut rng, 50.0));
+
+        assert!(buffer.sample(&mut rng, 32, 1.0, -1.0).is_err());
+        assert!(buffer.sample(&mut rng, 32, 1.0, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_generate_demonstrations() {
+        let steps = generate_demonstrations(9, 5, 1, &RewardConfig::default());
+
+        assert!(!steps.is_empty());
+        assert!(steps.iter().all(|step| step.is_demonstration));
+        assert!(steps.last().unwrap().game_result.is_some());
+    }
+
+    #[test]
+    fn test_count_unique_positions() {
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        board.set_cell(1, Cell::White);
+
+        // the same position rotated by 180 degrees
+        let mut rotated = Board::new(15);
+        rotated.set_cell(224, Cell::Black);
+        rotated.set_cell(223, Cell::White);
+
+        let steps = [
+            step_with_board(0, false, board),
+            step_with_board(0, false, rotated),
+        ];
+        assert_eq!(count_unique_positions(&steps), 1);
+
+        let mut centered = Board::new(15);
+        centered.set_cell(112, Cell::Black);
+
+        let steps = [step(0, false), step_with_board(0, false, centered)];
+        assert_eq!(count_unique_positions(&steps), 2);
+    }
+
+    #[test]
+    fn test_length_scaled_offensive_reward() {
+        let reward_config = RewardConfig {
+            offensive: vec![0f32, 0f32, 0f32, 0.01f32, 0.02f32, 0.03f32],
+            defensive: 0.01f32,
+            ..Default::default()
+        };
+
+        // black builds a line along the first row, white plays far away
+        let mut game = Game::new(15, 5);
+        for index in [0, 100, 1, 102] {
+            game.place_stone(index).unwrap();
+        }
+
+        let three = game.place_stone(2).unwrap();
+        game.place_stone(104).unwrap();
+        let four = game.place_stone(3).unwrap();
+
+        let three_reward = compute_nonterminal_reward(&three, &reward_config);
+        let four_reward = compute_nonterminal_reward(&four, &reward_config);
+
+        assert_eq!(three_reward, 0.01f32);
+        assert_eq!(four_reward, 0.02f32);
+        assert_eq!(
+            compute_nonterminal_reward(&four, &RewardConfig::default()),
+            three_reward
+        );
+    }
+
+    #[test]
+    fn test_step_penalty() {
+        let reward_config = RewardConfig {
+            step_penalty: 0.005f32,
+            ..Default::default()
+        };
+
+        let mut game = Game::new(15, 5);
+        let mut results = vec![];
+        for index in [0, 100, 1, 102, 2] {
+            results.push(game.place_stone(index).unwrap());
+        }
+
+        for result in &results {
+            let baseline = compute_nonterminal_reward(result, &RewardConfig::default());
+            let penalized = compute_nonterminal_reward(result, &reward_config);
+            assert_eq!(penalized, baseline - 0.005f32);
+        }
+    }
+
+    #[test]
+    fn test_step_to_game() {
+        let mut played = Game::new(15, 5);
+        for index in [112, 113, 97, 98, 82] {
+            played.place_stone(index).unwrap();
+        }
+
+        let step = ReplayStep {
+            turn: played.turn(),
+            action: 0,
+            boards: generate_history_boards(played.turn(), &played),
+            next_boards: None,
+            game_result: None,
+            reward: 0f32,
+            is_demonstration: false,
+        };
+        let game = step.to_game(15, 5);
+
+        assert_eq!(game.board(), &step.boards.last().unwrap().1);
+        assert_eq!(game.board(), played.board());
+        assert_eq!(game.turn(), Turn::White);
+        assert_eq!(game.game_result(), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! A minimal writer of TensorBoard event files, supporting scalar summaries only.
+//!
+//! Event files are sequences of TFRecords, each holding an `Event` protobuf message. The few
+//! messages and fields needed are encoded by hand to avoid depending on a protobuf library.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug)]
+pub struct EventWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl EventWriter {
+    /// Creates a new event file in `logdir`, creating the directory if needed.
+    pub fn new(logdir: impl AsRef<Path>) -> io::Result<Self> {
+        let logdir = logdir.as_ref();
+        std::fs::create_dir_all(logdir)?;
+
+        let path = logdir.join(format!(
+            "events.out.tfevents.{}.gomoku-ai.{}",
+            wall_time() as u64,
+            std::process::id()
+        ));
+        let mut writer = Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+        };
+
+        // the first event of a file declares its version
+        let mut event = encode_event_header(0);
+        encode_bytes(&mut event, 3, b"brain.Event:2");
+        writer.write_record(&event)?;
+        writer.writer.flush()?;
+
+        Ok(writer)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Logs a scalar value under `tag` at the given step.
+    pub fn add_scalar(&mut self, tag: &str, value: f32, step: i64) -> io::Result<()> {
+        // Summary.Value { tag = 1, simple_value = 2 }
+        let mut summary_value = vec![];
+        encode_bytes(&mut summary_value, 1, tag.as_bytes());
+        encode_key(&mut summary_value, 2, 5);
+        summary_value.extend(value.to_le_bytes());
+
+        // Summary { repeated Value value = 1 }
+        let mut summary = vec![];
+        encode_bytes(&mut summary, 1, &summary_value);
+
+        let mut event = encode_event_header(step);
+        encode_bytes(&mut event, 5, &summary);
+        self.write_record(&event)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> io::Result<()> {
+        let len = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&len)?;
+        self.writer.write_all(&masked_crc32c(&len).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// Encodes the `wall_time = 1` and `step = 2` fields of an `Event`.
+fn encode_event_header(step: i64) -> Vec<u8> {
+    let mut event = vec![];
+    encode_key(&mut event, 1, 1);
+    event.extend(wall_time().to_le_bytes());
+    encode_key(&mut event, 2, 0);
+    encode_varint(&mut event, step as u64);
+    event
+}
+
+fn encode_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    encode_varint(buf, (field << 3) | wire_type);
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    encode_key(buf, field, 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend(bytes);
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while 0x80 <= value {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    buf.push(value as u8);
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f63b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282ead8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+    }
+
+    #[test]
+    fn test_event_writer() {
+        let logdir = std::env::temp_dir().join(format!("gomoku-summary-{}", std::process::id()));
+        let mut writer = EventWriter::new(&logdir).unwrap();
+
+        for epoch in 0..2 {
+            writer.add_scalar("loss", 0.5, epoch).unwrap();
+            writer.add_scalar("win_rate", 0.25, epoch).unwrap();
+            writer.add_scalar("epsilon", 0.1, epoch).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let bytes = std::fs::read(writer.path()).unwrap();
+        std::fs::remove_dir_all(&logdir).unwrap();
+
+        // walk the records: the version header and 6 scalars
+        let mut records = 0;
+        let mut rest = &bytes[..];
+
+        while !rest.is_empty() {
+            let len = u64::from_le_bytes(rest[..8].try_into().unwrap()) as usize;
+            let data = &rest[12..12 + len];
+            let crc = u32::from_le_bytes(rest[12 + len..16 + len].try_into().unwrap());
+            assert_eq!(crc, masked_crc32c(data));
+
+            records += 1;
+            rest = &rest[16 + len..];
+        }
+
+        assert_eq!(records, 7);
+    }
+}
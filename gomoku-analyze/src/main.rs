@@ -0,0 +1,167 @@
+use gomoku_agent::{
+    agent::Agent,
+    agents::gomoku_ddqn::{GomokuDDQNAgent, GomokuDDQNProvider},
+};
+use gomoku_core::game::{Game, PlaceStoneError};
+use std::io::Write;
+
+const BOARD_SIZE: usize = 15;
+const MAX_CONSECUTIVE_STONES: usize = 5;
+
+fn main() {
+    let mut game = Game::new(BOARD_SIZE, MAX_CONSECUTIVE_STONES);
+    let mut agent = GomokuDDQNProvider.create_ddqn_agent();
+
+    println!("{}", game);
+
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+
+        if std::io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+
+        let input = input.trim();
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+
+        match command {
+            "" => {}
+            "quit" | "exit" => break,
+            "help" => help(),
+            "show" => println!("{}", game),
+            "undo" => undo(&mut game),
+            "hint" => hint(&agent, &game),
+            "eval" => eval(&agent, &game),
+            "load" => load(&mut agent, argument),
+            "save" => save(&game, argument),
+            _ => play(&mut game, input),
+        }
+    }
+}
+
+fn help() {
+    println!("commands:");
+    println!("  <position>   place a stone for the current player (e.g. h8)");
+    println!("  undo         take back the last move");
+    println!("  hint         show the agent's top moves");
+    println!("  eval         show the agent's evaluation of the position");
+    println!("  load <path>  load agent weights");
+    println!("  save <path>  save the game as SGF");
+    println!("  show         print the board");
+    println!("  quit         exit");
+}
+
+fn play(game: &mut Game, position: &str) {
+    if game.game_result().is_some() {
+        println!("game is over; undo to continue");
+        return;
+    }
+
+    let index = match game.board().parse_index(position) {
+        Some(index) => index,
+        None => {
+            println!("unknown command or invalid position: {}", position);
+            return;
+        }
+    };
+
+    match game.place_stone(index) {
+        Ok(_) => {
+            println!("{}", game);
+        }
+        Err(PlaceStoneError::InvalidIndex {
+            index,
+            max_allowed_index,
+        }) => {
+            println!(
+                "invalid index: {} (max allowed: {})",
+                index, max_allowed_index
+            );
+        }
+        Err(PlaceStoneError::StoneAlreadyPlaced { index, .. }) => {
+            println!(
+                "stone already placed at index: {}",
+                game.board().index_to_position(index).unwrap()
+            );
+        }
+    }
+}
+
+fn undo(game: &mut Game) {
+    let moves = game.moves();
+
+    if moves.is_empty() {
+        println!("nothing to undo");
+        return;
+    }
+
+    *game = Game::from_moves(
+        game.board_size(),
+        game.max_consecutive_stones(),
+        &moves[..moves.len() - 1],
+    )
+    .unwrap();
+    println!("{}", game);
+}
+
+fn hint(agent: &GomokuDDQNAgent, game: &Game) {
+    let mut probs = agent.move_win_probs(game);
+
+    if probs.is_empty() {
+        println!("no legal moves");
+        return;
+    }
+
+    probs.sort_by(|(_, lhs), (_, rhs)| f32::total_cmp(rhs, lhs));
+
+    for (index, prob) in probs.iter().take(3) {
+        println!(
+            "hint: {} ({:.4})",
+            game.board().index_to_position(*index).unwrap(),
+            prob
+        );
+    }
+}
+
+fn eval(agent: &GomokuDDQNAgent, game: &Game) {
+    let best = agent
+        .move_win_probs(game)
+        .into_iter()
+        .map(|(_, prob)| prob)
+        .max_by(f32::total_cmp);
+
+    match best {
+        Some(prob) => println!("eval: {:.4}", prob),
+        None => println!("no legal moves"),
+    }
+}
+
+fn load(agent: &mut GomokuDDQNAgent, path: &str) {
+    if path.is_empty() {
+        println!("usage: load <path>");
+        return;
+    }
+
+    match agent.load(path) {
+        Ok(()) => println!("loaded agent from {}", path),
+        Err(err) => println!("failed to load agent: {}", err),
+    }
+}
+
+fn save(game: &Game, path: &str) {
+    if path.is_empty() {
+        println!("usage: save <path>");
+        return;
+    }
+
+    match std::fs::write(path, game.to_sgf()) {
+        Ok(()) => println!("saved game to {}", path),
+        Err(err) => println!("failed to save game: {}", err),
+    }
+}
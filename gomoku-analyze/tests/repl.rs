@@ -0,0 +1,39 @@
+use gomoku_core::game::Game;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+#[test]
+fn test_command_script() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gomoku-analyze"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"h8\ni8\nh9\nundo\nh10\nshow\neval\nquit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let mut expected = Game::new(15, 5);
+
+    for position in ["h8", "i8", "h10"] {
+        let index = expected.board().parse_index(position).unwrap();
+        expected.place_stone(index).unwrap();
+    }
+
+    assert!(stdout.contains(&expected.to_string()));
+
+    let eval = stdout
+        .lines()
+        .find_map(|line| line.split_once("eval: ").map(|(_, value)| value))
+        .unwrap();
+    assert!(eval.trim().parse::<f32>().is_ok());
+}
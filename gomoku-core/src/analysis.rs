@@ -0,0 +1,119 @@
+use crate::game::{Game, GameConfigError, GameResult, PlaceStoneError};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum GameValidationError {
+    #[error(transparent)]
+    InvalidConfig(#[from] GameConfigError),
+    #[error("illegal move at index {move_index}: {source}")]
+    IllegalMove {
+        move_index: usize,
+        source: PlaceStoneError,
+    },
+    #[error("move at index {move_index} is played after the game ended")]
+    MoveAfterEnd { move_index: usize },
+    #[error("the game is not finished after {moves} moves")]
+    Unfinished { moves: usize },
+}
+
+/// Checks that the moves form a legal, finished game and returns its result.
+///
+/// The moves alternate between black and white starting with black. Move indices in errors are
+/// 0-based positions in `moves`.
+pub fn validate_game(
+    board_size: usize,
+    win_len: usize,
+    moves: &[usize],
+) -> Result<GameResult, GameValidationError> {
+    let mut game = Game::try_new(board_size, win_len)?;
+
+    for (move_index, &index) in moves.iter().enumerate() {
+        if game.game_result().is_some() {
+            return Err(GameValidationError::MoveAfterEnd { move_index });
+        }
+
+        game.place_stone(index)
+            .map_err(|source| GameValidationError::IllegalMove { move_index, source })?;
+    }
+
+    game.game_result()
+        .ok_or(GameValidationError::Unfinished { moves: moves.len() })
+}
+
+/// Checks whether the game was won only because the loser skipped a mandatory block.
+///
+/// At every move of the loser, the cells where the winner could have won next (see
+/// `Board::blocking_moves`) are computed; the game is flagged if the loser played elsewhere while
+/// there was something to block. Unfinished and drawn games are never flagged.
+pub fn was_forced_win(game: &Game) -> bool {
+    let loser = match game.game_result() {
+        Some(GameResult::Win(winner)) => winner.next(),
+        _ => return false,
+    };
+
+    game.history()
+        .iter()
+        .zip(game.moves())
+        .filter(|((turn, _), _)| *turn == loser)
+        .any(|((_, board), index)| {
+            let blocking_moves = board.blocking_moves(loser, game.max_consecutive_stones());
+            !blocking_moves.is_empty() && !blocking_moves.contains(index)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Turn;
+
+    #[test]
+    fn test_validate_game() {
+        assert!(matches!(
+            validate_game(15, 5, &[0, 15, 1, 16, 2, 17, 3, 18, 4]),
+            Ok(GameResult::Win(Turn::Black))
+        ));
+        assert!(matches!(
+            validate_game(15, 5, &[0, 15, 1, 16, 2, 17, 3, 18, 4, 19]),
+            Err(GameValidationError::MoveAfterEnd { move_index: 9 })
+        ));
+        assert!(matches!(
+            validate_game(15, 5, &[0, 15, 1, 15]),
+            Err(GameValidationError::IllegalMove {
+                move_index: 3,
+                source: PlaceStoneError::StoneAlreadyPlaced { index: 15, .. },
+            })
+        ));
+        assert!(matches!(
+            validate_game(15, 5, &[0, 225]),
+            Err(GameValidationError::IllegalMove {
+                move_index: 1,
+                source: PlaceStoneError::InvalidIndex { index: 225, .. },
+            })
+        ));
+        assert!(matches!(
+            validate_game(3, 5, &[0, 1, 2]),
+            Err(GameValidationError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            validate_game(15, 5, &[0, 15, 1]),
+            Err(GameValidationError::Unfinished { moves: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_was_forced_win() {
+        // black makes an open four on row 7 (columns 3-6), white ignores it
+        let game = Game::from_moves(15, 5, &[108, 0, 109, 30, 110, 60, 111, 90, 112]).unwrap();
+        assert!(game.game_result().is_some());
+        assert!(was_forced_win(&game));
+
+        // white blocks one end of the open four, but black wins at the other
+        let game = Game::from_moves(15, 5, &[108, 0, 109, 30, 110, 60, 111, 107, 112]).unwrap();
+        assert!(game.game_result().is_some());
+        assert!(!was_forced_win(&game));
+
+        // unfinished games are never flagged
+        let game = Game::from_moves(15, 5, &[108, 0, 109, 30, 110, 60, 111, 90]).unwrap();
+        assert!(!was_forced_win(&game));
+    }
+}
@@ -1,8 +1,22 @@
+mod formatter;
 mod index_parser;
+mod packed;
+mod symmetry;
+mod threat;
+
+pub use formatter::{format_move, BoardFormatter};
+pub use packed::PackedBoardError;
+pub use symmetry::Symmetry;
+pub use threat::{LineId, Threat, ThreatKind};
 
 use crate::game::Turn;
-use index_parser::IndexParser;
-use std::{cmp::Reverse, fmt::Display};
+use index_parser::{Index, IndexParser};
+use std::{
+    cmp::Reverse,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    iter,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Cell {
@@ -39,18 +53,59 @@ impl Cell {
             Cell::White => 'O',
         }
     }
+
+    /// Returns the cell with the opposite color. Empty cells stay empty.
+    pub fn swapped(self) -> Self {
+        match self {
+            Cell::Empty => Cell::Empty,
+            Cell::Black => Cell::White,
+            Cell::White => Cell::Black,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Where a cell lies on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellPosition {
+    Corner,
+    Edge,
+    Interior,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Board {
     board_size: usize,
     cells: Vec<Cell>,
+    /// A bitset of the empty cells, 64 cells per word, kept in sync with `cells` by `set_cell`.
+    /// Kept flat so that cloning a board stays cheap.
+    empty: Vec<u64>,
+    /// The number of bits set in `empty`.
+    empty_count: usize,
 }
 
 impl Board {
     pub fn new(board_size: usize) -> Self {
         let cells = vec![Cell::Empty; board_size * board_size];
-        Self { board_size, cells }
+        Self::from_cells(board_size, cells)
+    }
+
+    fn from_cells(board_size: usize, cells: Vec<Cell>) -> Self {
+        let mut empty = vec![0u64; cells.len().div_ceil(64)];
+        let mut empty_count = 0;
+
+        for (index, cell) in cells.iter().enumerate() {
+            if cell.is_empty() {
+                empty[index / 64] |= 1 << (index % 64);
+                empty_count += 1;
+            }
+        }
+
+        Self {
+            board_size,
+            cells,
+            empty,
+            empty_count,
+        }
     }
 
     pub fn board_size(&self) -> usize {
@@ -61,12 +116,30 @@ impl Board {
         &self.cells
     }
 
+    /// Returns the empty cells in ascending order.
+    ///
+    /// The empty cells are tracked as stones are placed and removed, so this doesn't scan the
+    /// occupied cells.
     pub fn legal_moves(&self) -> Vec<usize> {
-        self.cells
+        self.legal_moves_iter().collect()
+    }
+
+    /// Iterates over the empty cells in ascending order, without allocating.
+    pub fn legal_moves_iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.empty
             .iter()
             .enumerate()
-            .filter_map(|(index, cell)| if cell.is_empty() { Some(index) } else { None })
-            .collect()
+            .flat_map(|(word_index, &word)| {
+                // peel off the lowest set bit until none is left
+                iter::successors(Some(word), |&word| Some(word & word.wrapping_sub(1)))
+                    .take_while(|&word| word != 0)
+                    .map(move |word| word_index * 64 + word.trailing_zeros() as usize)
+            })
+    }
+
+    /// Returns the number of empty cells, tracked along with the empty cells themselves.
+    pub fn empty_count(&self) -> usize {
+        self.empty_count
     }
 
     pub fn illegal_moves(&self) -> Vec<usize> {
@@ -77,12 +150,173 @@ impl Board {
             .collect()
     }
 
+    /// Returns the indices of the cells holding `cell`, in ascending order.
+    pub fn stones(&self, cell: Cell) -> Vec<usize> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &other)| if other == cell { Some(index) } else { None })
+            .collect()
+    }
+
+    /// Groups the cells holding `cell` into 8-connected components, found by flood fill over
+    /// `neighbors`. Each group is sorted, and groups are ordered by their first index.
+    pub fn groups(&self, cell: Cell) -> Vec<Vec<usize>> {
+        let mut visited = vec![false; self.cells.len()];
+        let mut groups = vec![];
+
+        for start in self.stones(cell) {
+            if visited[start] {
+                continue;
+            }
+
+            visited[start] = true;
+            let mut group = vec![];
+            let mut stack = vec![start];
+
+            while let Some(index) = stack.pop() {
+                group.push(index);
+
+                for neighbor in self.neighbors(index) {
+                    if !visited[neighbor] && self.cells[neighbor] == cell {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+
+            group.sort_unstable();
+            groups.push(group);
+        }
+
+        groups
+    }
+
+    /// Returns a mask of length `size²` where `true` marks the cells a stone can be placed on.
+    pub fn legal_mask(&self) -> Vec<bool> {
+        self.cells.iter().map(|cell| cell.is_empty()).collect()
+    }
+
+    /// Returns the legal moves within `distance` cells (in any direction, diagonals included) of an
+    /// existing stone. On an empty board, the center is the only candidate.
+    pub fn candidate_moves(&self, distance: usize) -> Vec<usize> {
+        if self.empty_count() == self.cells.len() {
+            let center = self.board_size / 2;
+            return self.to_index(center, center).into_iter().collect();
+        }
+
+        let distance = distance as isize;
+        let size = self.board_size as isize;
+
+        self.legal_moves()
+            .into_iter()
+            .filter(|&index| {
+                let (row, column) = self.to_coords(index).unwrap();
+                let (row, column) = (row as isize, column as isize);
+
+                (-distance..=distance).any(|dy| {
+                    (-distance..=distance).any(|dx| {
+                        let (y, x) = (row + dy, column + dx);
+                        0 <= y
+                            && y < size
+                            && 0 <= x
+                            && x < size
+                            && !self.cells[(y * size + x) as usize].is_empty()
+                    })
+                })
+            })
+            .collect()
+    }
+
     pub fn get_cell(&self, index: usize) -> Option<Cell> {
         self.cells.get(index).copied()
     }
 
     pub fn set_cell(&mut self, index: usize, cell: Cell) {
         self.cells[index] = cell;
+
+        let word = &mut self.empty[index / 64];
+        let bit = 1 << (index % 64);
+        let was_empty = *word & bit != 0;
+
+        if cell.is_empty() {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+
+        match (was_empty, cell.is_empty()) {
+            (false, true) => self.empty_count += 1,
+            (true, false) => self.empty_count -= 1,
+            _ => {}
+        }
+    }
+
+    /// Converts a `(row, column)` pair into a board index, or `None` if it is out of the board.
+    pub fn to_index(&self, row: usize, column: usize) -> Option<usize> {
+        let index = Index { row, column };
+
+        if !index.is_valid(self.board_size) {
+            return None;
+        }
+
+        Some(index.to_index(self.board_size))
+    }
+
+    /// Converts a board index into a `(row, column)` pair, or `None` if it is out of the board.
+    pub fn to_coords(&self, index: usize) -> Option<(usize, usize)> {
+        if self.cells.len() <= index {
+            return None;
+        }
+
+        let index = Index::from_index(index, self.board_size);
+        Some((index.row, index.column))
+    }
+
+    /// Returns the index of the center cell, the lower right of the four middle cells on an
+    /// even-sized board.
+    pub fn center_index(&self) -> usize {
+        self.board_size / 2 * self.board_size + self.board_size / 2
+    }
+
+    /// Tells whether the cell at `index` is a corner, on an edge or in the interior of the board.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of the board.
+    pub fn cell_kind(&self, index: usize) -> CellPosition {
+        let (row, column) = self.to_coords(index).expect("index out of the board");
+        let last = self.board_size - 1;
+        let on_edge = |coord| coord == 0 || coord == last;
+
+        match (on_edge(row), on_edge(column)) {
+            (true, true) => CellPosition::Corner,
+            (true, false) | (false, true) => CellPosition::Edge,
+            (false, false) => CellPosition::Interior,
+        }
+    }
+
+    /// Returns the indices of the up to 8 cells around `index`, diagonals included, in ascending
+    /// order. Returns nothing if `index` is out of the board.
+    pub fn neighbors(&self, index: usize) -> Vec<usize> {
+        let Some((row, column)) = self.to_coords(index) else {
+            return vec![];
+        };
+        let mut neighbors = Vec::with_capacity(8);
+
+        for y in row.saturating_sub(1)..=row + 1 {
+            for x in column.saturating_sub(1)..=column + 1 {
+                if (y, x) == (row, column) {
+                    continue;
+                }
+
+                if let Some(neighbor) = self.to_index(y, x) {
+                    neighbors.push(neighbor);
+                }
+            }
+        }
+
+        neighbors
     }
 
     /// Parses a string index into a board index.
@@ -111,12 +345,7 @@ impl Board {
     /// - 16 -> B1
     /// - 25 -> Z1
     pub fn index_to_position(&self, index: usize) -> Option<String> {
-        if self.board_size * self.board_size <= index {
-            return None;
-        }
-
-        let mut x = index % self.board_size;
-        let y = index / self.board_size;
+        let (y, mut x) = self.to_coords(index)?;
 
         let mut alpha = String::new();
 
@@ -180,6 +409,51 @@ impl Display for Board {
 }
 
 impl Board {
+    /// Checks whether the given player has a line of exactly `win_len` stones anywhere on the board.
+    pub fn is_won(&self, turn: Turn, win_len: usize) -> bool {
+        (0..self.cells.len()).any(|index| self.completes_line(index, turn, win_len))
+    }
+
+    /// Checks whether the stone at `index` is part of a line of exactly `win_len` stones of the
+    /// given player.
+    ///
+    /// Agrees with `count_consecutive_cells(index, turn).contains(&win_len)`, but each direction
+    /// stops walking once its line is known to be longer than `win_len` and no counts are
+    /// collected.
+    pub fn completes_line(&self, index: usize, turn: Turn, win_len: usize) -> bool {
+        if self.cells.get(index).copied() != Some(turn.into()) {
+            return false;
+        }
+
+        let cell = turn.into();
+        let x = (index % self.board_size) as isize;
+        let y = (index / self.board_size) as isize;
+
+        [(1, 0), (0, 1), (1, -1), (1, 1)]
+            .into_iter()
+            .any(|(x_delta, y_delta)| {
+                // walking at most `win_len + 1` stones in total tells an exact line from a longer one
+                let forward = self.count_consecutive_cells_in_direction(
+                    x + x_delta,
+                    y + y_delta,
+                    cell,
+                    x_delta,
+                    y_delta,
+                    win_len,
+                );
+                let backward = self.count_consecutive_cells_in_direction(
+                    x - x_delta,
+                    y - y_delta,
+                    cell,
+                    -x_delta,
+                    -y_delta,
+                    win_len - forward,
+                );
+
+                1 + forward + backward == win_len
+            })
+    }
+
     /// Count the number of consecutive cells in all directions for a given position and turn.
     ///
     /// Returns a vector of counts, sorted in descending order. It is useful to check
@@ -200,22 +474,34 @@ impl Board {
             return vec![];
         }
 
+        self.count_consecutive_cells_placing(index, turn)
+    }
+
+    /// Counts like `count_consecutive_cells`, but as if a stone of `turn` were at `index` whatever
+    /// the cell holds, so the counts of a move can be had from the board before it without a copy.
+    /// Returns nothing if `index` is out of the board.
+    pub fn count_consecutive_cells_placing(&self, index: usize, turn: Turn) -> Vec<usize> {
+        if self.cells.len() <= index {
+            return vec![];
+        }
+
+        let cell = turn.into();
         let x = (index % self.board_size) as isize;
         let y = (index / self.board_size) as isize;
 
         let mut results = vec![
             // case 1: horizontal
-            1 + self.count_consecutive_cells_in_direction(x + 1, y, cell, 1, 0)
-                + self.count_consecutive_cells_in_direction(x - 1, y, cell, -1, 0),
+            1 + self.count_consecutive_cells_in_direction(x + 1, y, cell, 1, 0, usize::MAX)
+                + self.count_consecutive_cells_in_direction(x - 1, y, cell, -1, 0, usize::MAX),
             // case 2: vertical
-            1 + self.count_consecutive_cells_in_direction(x, y + 1, cell, 0, 1)
-                + self.count_consecutive_cells_in_direction(x, y - 1, cell, 0, -1),
+            1 + self.count_consecutive_cells_in_direction(x, y + 1, cell, 0, 1, usize::MAX)
+                + self.count_consecutive_cells_in_direction(x, y - 1, cell, 0, -1, usize::MAX),
             // case 3: diagonal left-up
-            1 + self.count_consecutive_cells_in_direction(x + 1, y - 1, cell, 1, -1)
-                + self.count_consecutive_cells_in_direction(x - 1, y + 1, cell, -1, 1),
+            1 + self.count_consecutive_cells_in_direction(x + 1, y - 1, cell, 1, -1, usize::MAX)
+                + self.count_consecutive_cells_in_direction(x - 1, y + 1, cell, -1, 1, usize::MAX),
             // case 4: diagonal right-up
-            1 + self.count_consecutive_cells_in_direction(x + 1, y + 1, cell, 1, 1)
-                + self.count_consecutive_cells_in_direction(x - 1, y - 1, cell, -1, -1),
+            1 + self.count_consecutive_cells_in_direction(x + 1, y + 1, cell, 1, 1, usize::MAX)
+                + self.count_consecutive_cells_in_direction(x - 1, y - 1, cell, -1, -1, usize::MAX),
         ];
 
         results.sort_unstable_by_key(|&count| Reverse(count));
@@ -238,12 +524,18 @@ impl Board {
         cell: Cell,
         x_delta: isize,
         y_delta: isize,
+        limit: usize,
     ) -> usize {
         let mut count = 0;
         let mut x = x;
         let mut y = y;
 
-        while x >= 0 && x < self.board_size as isize && y >= 0 && y < self.board_size as isize {
+        while count < limit
+            && x >= 0
+            && x < self.board_size as isize
+            && y >= 0
+            && y < self.board_size as isize
+        {
             let index = (y * self.board_size as isize + x) as usize;
 
             if self.cells[index] != cell {
@@ -259,9 +551,466 @@ impl Board {
     }
 }
 
+impl Board {
+    /// Returns a copy of the board transformed by the given symmetry.
+    ///
+    /// The stone at `index` ends up at `symmetry.map_index(index, board_size)`.
+    pub fn apply_symmetry(&self, symmetry: Symmetry) -> Board {
+        let mut board = Board::new(self.board_size);
+
+        for (index, &cell) in self.cells.iter().enumerate() {
+            board.set_cell(symmetry.map_index(index, self.board_size), cell);
+        }
+
+        board
+    }
+
+    /// Returns one legal move per class of moves that are equivalent under the symmetries leaving the
+    /// board unchanged.
+    ///
+    /// The representative of each class is its smallest index. On an empty board all 8 symmetries
+    /// apply, while on most non-trivial positions only the identity does and every legal move is kept.
+    pub fn canonical_legal_moves(&self) -> Vec<usize> {
+        let symmetries = Symmetry::ALL
+            .into_iter()
+            .filter(|&symmetry| self.apply_symmetry(symmetry) == *self)
+            .collect::<Vec<_>>();
+
+        self.legal_moves()
+            .into_iter()
+            .filter(|&index| {
+                symmetries
+                    .iter()
+                    .all(|symmetry| index <= symmetry.map_index(index, self.board_size))
+            })
+            .collect()
+    }
+
+    /// Returns the symmetry taking the board to its lexicographically smallest rotation/reflection,
+    /// the earliest in `Symmetry::ALL` if several do. Colors are kept as they are.
+    pub fn canonical_symmetry(&self) -> Symmetry {
+        Symmetry::ALL
+            .into_iter()
+            .map(|symmetry| (self.apply_symmetry(symmetry).cells, symmetry))
+            .min()
+            .map(|(_, symmetry)| symmetry)
+            .unwrap()
+    }
+
+    /// Returns a hash shared by all positions strategically equivalent to this one.
+    ///
+    /// A position is equivalent to its 8 rotations/reflections and to the color-swapped form of each
+    /// of them. The hash is computed over the lexicographically smallest of those 16 boards.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut canonical: Option<Vec<Cell>> = None;
+
+        for symmetry in Symmetry::ALL {
+            let transformed = self.apply_symmetry(symmetry);
+
+            for swap_colors in [false, true] {
+                let cells = if swap_colors {
+                    transformed
+                        .cells
+                        .iter()
+                        .map(|cell| cell.swapped())
+                        .collect()
+                } else {
+                    transformed.cells.clone()
+                };
+
+                if canonical
+                    .as_ref()
+                    .is_none_or(|canonical| cells < *canonical)
+                {
+                    canonical = Some(cells);
+                }
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        self.board_size.hash(&mut hasher);
+        canonical.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Board {
+    /// Returns the fewest additional stones `turn` needs to complete a line of `win_len`, ignoring
+    /// any interference by the opponent.
+    ///
+    /// Every window of `win_len` cells along a row, column or diagonal is considered, except those
+    /// already containing an opponent stone. Returns `None` if no such window exists.
+    pub fn min_stones_to_win(&self, turn: Turn, win_len: usize) -> Option<usize> {
+        let own = Cell::from(turn);
+        let opponent = Cell::from(turn.next());
+
+        self.line_windows(win_len)
+            .into_iter()
+            .filter(|window| window.iter().all(|&index| self.cells[index] != opponent))
+            .map(|window| {
+                window
+                    .iter()
+                    .filter(|&&index| self.cells[index] != own)
+                    .count()
+            })
+            .min()
+    }
+
+    /// Returns the legal move giving `turn` the longest line through it, as a greedy baseline that
+    /// ignores the opponent. Lines longer than `win_len` don't win and count as nothing.
+    ///
+    /// Ties go to the move closest to the center, then to the lowest index. Returns `None` if the
+    /// board is full.
+    pub fn greedy_extension_move(&self, turn: Turn, win_len: usize) -> Option<usize> {
+        let mut board = self.clone();
+        // doubled coordinates keep the center integral on even-sized boards
+        let center = self.board_size as isize - 1;
+
+        self.legal_moves_iter().min_by_key(|&index| {
+            board.set_cell(index, turn.into());
+            let length = board
+                .count_consecutive_cells(index, turn)
+                .into_iter()
+                .find(|&length| length <= win_len)
+                .unwrap_or(1);
+            board.set_cell(index, Cell::Empty);
+
+            let row = 2 * (index / self.board_size) as isize - center;
+            let column = 2 * (index % self.board_size) as isize - center;
+            (Reverse(length), row * row + column * column)
+        })
+    }
+
+    /// Lists every run of `window_len` consecutive cells along a row, column or diagonal.
+    ///
+    /// The indices within each window are ordered along the line's direction.
+    fn line_windows(&self, window_len: usize) -> Vec<Vec<usize>> {
+        let size = self.board_size as isize;
+        let len = window_len as isize;
+        let mut windows = vec![];
+
+        if window_len == 0 {
+            return windows;
+        }
+
+        for (x_delta, y_delta) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for y in 0..size {
+                for x in 0..size {
+                    let end_x = x + x_delta * (len - 1);
+                    let end_y = y + y_delta * (len - 1);
+
+                    if end_x < 0 || size <= end_x || end_y < 0 || size <= end_y {
+                        continue;
+                    }
+
+                    windows.push(
+                        (0..len)
+                            .map(|i| ((y + y_delta * i) * size + x + x_delta * i) as usize)
+                            .collect(),
+                    );
+                }
+            }
+        }
+
+        windows
+    }
+}
+
+/// Fills a board with stones in pairs along each row, shifted by one pair on every row, so that no
+/// row, column or diagonal has more than two stones of a color in a row. The board is full and
+/// won by nobody for any win length of 3 or more, which makes it a ready-made draw.
+#[cfg(test)]
+pub(crate) fn fill_draw_board(board_size: usize) -> Board {
+    let mut board = Board::new(board_size);
+
+    for index in 0..board_size * board_size {
+        let (row, column) = (index / board_size, index % board_size);
+        let cell = if (column / 2 + row) % 2 == 0 {
+            Cell::Black
+        } else {
+            Cell::White
+        };
+        board.set_cell(index, cell);
+    }
+
+    board
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_fill_draw_board() {
+        for board_size in [3, 4, 9, 15] {
+            let board = fill_draw_board(board_size);
+            assert!(board.legal_moves().is_empty());
+
+            for win_len in 3..=board_size {
+                assert!(!board.is_won(Turn::Black, win_len));
+                assert!(!board.is_won(Turn::White, win_len));
+            }
+
+            for index in 0..board_size * board_size {
+                for turn in [Turn::Black, Turn::White] {
+                    let longest = board.count_consecutive_cells(index, turn).first().copied();
+                    assert!(longest.unwrap_or(0) <= 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_mask() {
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        board.set_cell(112, Cell::White);
+        board.set_cell(113, Cell::Black);
+        board.set_cell(224, Cell::White);
+
+        let mask = board.legal_mask();
+        assert_eq!(mask.len(), 15 * 15);
+
+        let legal_moves = mask
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &legal)| if legal { Some(index) } else { None })
+            .collect::<Vec<_>>();
+        assert_eq!(legal_moves, board.legal_moves());
+    }
+
+    #[test]
+    fn test_legal_moves_tracking() {
+        let full_scan = |board: &Board| {
+            board
+                .cells()
+                .iter()
+                .enumerate()
+                .filter_map(|(index, cell)| if cell.is_empty() { Some(index) } else { None })
+                .collect::<Vec<_>>()
+        };
+
+        let mut board = Board::new(15);
+        assert_eq!(board.legal_moves(), full_scan(&board));
+
+        for (i, index) in [112, 113, 0, 224, 98, 14].into_iter().enumerate() {
+            let cell = if i % 2 == 0 { Cell::Black } else { Cell::White };
+            board.set_cell(index, cell);
+            assert_eq!(board.legal_moves(), full_scan(&board));
+        }
+
+        // undo some moves, and overwrite a stone with another
+        for index in [14, 0, 113] {
+            board.set_cell(index, Cell::Empty);
+            assert_eq!(board.legal_moves(), full_scan(&board));
+        }
+
+        board.set_cell(112, Cell::White);
+        board.set_cell(112, Cell::Empty);
+        board.set_cell(112, Cell::Empty);
+        assert_eq!(board.legal_moves(), full_scan(&board));
+        assert_eq!(board.legal_moves_iter().count(), 225 - 2);
+        assert_eq!(board.empty_count(), 225 - 2);
+
+        let packed = Board::from_packed(15, &board.to_packed()).unwrap();
+        assert_eq!(packed.legal_moves(), full_scan(&board));
+        assert_eq!(packed, board);
+
+        // boards whose cells end on, and right after, a word boundary of the tracked set
+        for board_size in [8, 9] {
+            let mut board = Board::new(board_size);
+            let last = board_size * board_size - 1;
+
+            for index in [0, 63, 64, last] {
+                if index <= last {
+                    board.set_cell(index, Cell::Black);
+                }
+            }
+            assert_eq!(board.legal_moves(), full_scan(&board));
+            assert_eq!(board.empty_count(), full_scan(&board).len());
+        }
+    }
+
+    #[test]
+    fn test_stones() {
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        board.set_cell(112, Cell::White);
+        board.set_cell(113, Cell::Black);
+        board.set_cell(224, Cell::White);
+
+        assert_eq!(board.stones(Cell::Black), vec![0, 113]);
+        assert_eq!(board.stones(Cell::White), vec![112, 224]);
+        assert_eq!(board.stones(Cell::Empty), board.legal_moves());
+    }
+
+    #[test]
+    fn test_greedy_extension_move() {
+        let mut board = Board::new(15);
+        assert_eq!(board.greedy_extension_move(Turn::Black, 5), Some(112));
+
+        // a black three on row 2, far from the center, and a lone white stone at the center
+        for index in [31, 32, 33] {
+            board.set_cell(index, Cell::Black);
+        }
+        board.set_cell(112, Cell::White);
+
+        // both ends make four; column 4 is closer to the center than column 0
+        assert_eq!(board.greedy_extension_move(Turn::Black, 5), Some(34));
+
+        // an overline doesn't count: black 5 on row 4 with a gap, closing it would make six
+        let mut board = Board::new(15);
+        for index in [60, 61, 62, 64, 65] {
+            board.set_cell(index, Cell::Black);
+        }
+        assert_ne!(board.greedy_extension_move(Turn::Black, 5), Some(63));
+
+        let mut board = Board::new(1);
+        board.set_cell(0, Cell::Black);
+        assert_eq!(board.greedy_extension_move(Turn::Black, 5), None);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let board = Board::new(15);
+
+        assert_eq!(board.neighbors(0), vec![1, 15, 16]);
+        assert_eq!(board.neighbors(7), vec![6, 8, 21, 22, 23]);
+        assert_eq!(
+            board.neighbors(112),
+            vec![96, 97, 98, 111, 113, 126, 127, 128]
+        );
+        assert!(board.neighbors(225).is_empty());
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut board = Board::new(15);
+
+        // a diagonal chain of three in the top left corner, and a pair in the middle
+        for index in [0, 16, 32, 112, 113] {
+            board.set_cell(index, Cell::Black);
+        }
+        board.set_cell(17, Cell::White);
+
+        assert_eq!(
+            board.groups(Cell::Black),
+            vec![vec![0, 16, 32], vec![112, 113]]
+        );
+        assert_eq!(board.groups(Cell::White), vec![vec![17]]);
+    }
+
+    #[test]
+    fn test_packed_round_trip() {
+        for board_size in [1, 2, 3, 9, 15, 19] {
+            let mut board = Board::new(board_size);
+
+            for index in 0..board_size * board_size {
+                let cell = match index % 3 {
+                    0 => Cell::Black,
+                    1 => Cell::Empty,
+                    _ => Cell::White,
+                };
+                board.set_cell(index, cell);
+            }
+
+            let packed = board.to_packed();
+            assert_eq!(packed.len(), (board_size * board_size).div_ceil(4));
+            assert_eq!(Board::from_packed(board_size, &packed), Ok(board));
+        }
+    }
+
+    #[test]
+    fn test_packed_invalid() {
+        let packed = Board::new(15).to_packed();
+        assert_eq!(packed.len(), 57);
+
+        assert_eq!(
+            Board::from_packed(15, &packed[..56]),
+            Err(PackedBoardError::InvalidLength {
+                expected: 57,
+                actual: 56
+            })
+        );
+        assert_eq!(
+            Board::from_packed(14, &packed),
+            Err(PackedBoardError::InvalidLength {
+                expected: 49,
+                actual: 57
+            })
+        );
+
+        let mut packed = packed;
+        packed[1] = 0b0000_1100;
+        assert_eq!(
+            Board::from_packed(15, &packed),
+            Err(PackedBoardError::InvalidCell { index: 5, value: 3 })
+        );
+    }
+
+    #[test]
+    fn test_candidate_moves() {
+        let mut board = Board::new(15);
+        assert_eq!(board.candidate_moves(1), vec![112]);
+
+        board.set_cell(0, Cell::Black);
+        assert_eq!(board.candidate_moves(1), vec![1, 15, 16]);
+
+        board.set_cell(112, Cell::White);
+        assert_eq!(board.candidate_moves(1).len(), 3 + 8);
+        assert_eq!(board.candidate_moves(2).len(), 8 + 24);
+    }
+
+    #[test]
+    fn test_index_coords_round_trip() {
+        let board = Board::new(15);
+
+        for index in 0..15 * 15 {
+            let (row, column) = board.to_coords(index).unwrap();
+            assert_eq!(board.to_index(row, column), Some(index));
+        }
+
+        assert_eq!(board.to_index(0, 0), Some(0));
+        assert_eq!(board.to_index(7, 7), Some(112));
+        assert_eq!(board.to_index(14, 14), Some(224));
+        assert_eq!(board.to_coords(16), Some((1, 1)));
+        assert_eq!(board.to_coords(224), Some((14, 14)));
+    }
+
+    #[test]
+    fn test_center_index() {
+        assert_eq!(Board::new(15).center_index(), 112);
+        assert_eq!(Board::new(4).center_index(), 10);
+    }
+
+    #[test]
+    fn test_cell_kind() {
+        let board = Board::new(15);
+
+        for index in [0, 14, 210, 224] {
+            assert_eq!(board.cell_kind(index), CellPosition::Corner);
+        }
+
+        for index in [1, 7, 13, 15, 29, 209, 220] {
+            assert_eq!(board.cell_kind(index), CellPosition::Edge);
+        }
+
+        for index in [16, 112, 208] {
+            assert_eq!(board.cell_kind(index), CellPosition::Interior);
+        }
+    }
+
+    #[test]
+    fn test_index_coords_out_of_range() {
+        let board = Board::new(15);
+
+        assert_eq!(board.to_index(15, 0), None);
+        assert_eq!(board.to_index(0, 15), None);
+        assert_eq!(board.to_index(usize::MAX, usize::MAX), None);
+        assert_eq!(board.to_coords(225), None);
+        assert_eq!(board.to_coords(usize::MAX), None);
+    }
 
     #[test]
     fn test_count_consecutive_cells() {
@@ -269,20 +1018,20 @@ mod tests {
         let mut board = Board::new(15);
 
         // Place a horizontal line of black stones
-        board.cells[0] = Cell::Black;
-        board.cells[1] = Cell::Black;
-        board.cells[2] = Cell::Black;
-        board.cells[3] = Cell::Black;
+        board.set_cell(0, Cell::Black);
+        board.set_cell(1, Cell::Black);
+        board.set_cell(2, Cell::Black);
+        board.set_cell(3, Cell::Black);
 
         // Place a vertical line of white stones
-        board.cells[15] = Cell::White;
-        board.cells[30] = Cell::White;
-        board.cells[45] = Cell::White;
+        board.set_cell(15, Cell::White);
+        board.set_cell(30, Cell::White);
+        board.set_cell(45, Cell::White);
 
         // Place a diagonal line of black stones
-        board.cells[16] = Cell::Black;
-        board.cells[32] = Cell::Black;
-        board.cells[48] = Cell::Black;
+        board.set_cell(16, Cell::Black);
+        board.set_cell(32, Cell::Black);
+        board.set_cell(48, Cell::Black);
 
         println!("{}", board);
 
@@ -302,4 +1051,218 @@ mod tests {
         let results = board.count_consecutive_cells(230, Turn::Black);
         assert_eq!(results, vec![]);
     }
+
+    #[test]
+    fn test_count_consecutive_cells_placing() {
+        let mut board = Board::new(15);
+        for index in [0, 1, 3, 16, 32] {
+            board.set_cell(index, Cell::Black);
+        }
+
+        // filling the gap joins the row, and makes a pair with the stone diagonally below
+        assert_eq!(
+            board.count_consecutive_cells_placing(2, Turn::Black),
+            vec![4, 2]
+        );
+        assert_eq!(
+            board.count_consecutive_cells_placing(48, Turn::Black),
+            vec![4]
+        );
+        assert_eq!(
+            board.count_consecutive_cells_placing(2, Turn::White),
+            vec![]
+        );
+        assert_eq!(
+            board.count_consecutive_cells_placing(225, Turn::Black),
+            vec![]
+        );
+
+        for index in [2, 48, 100] {
+            let mut placed = board.clone();
+            placed.set_cell(index, Cell::Black);
+            assert_eq!(
+                board.count_consecutive_cells_placing(index, Turn::Black),
+                placed.count_consecutive_cells(index, Turn::Black)
+            );
+        }
+    }
+
+    #[test]
+    fn test_completes_line_matches_count_consecutive_cells() {
+        // a small linear congruential generator keeps the positions reproducible
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % bound
+        };
+
+        for _ in 0..200 {
+            let mut board = Board::new(9);
+            let stones = next(60);
+
+            for _ in 0..stones {
+                let cell = match next(2) {
+                    0 => Cell::Black,
+                    _ => Cell::White,
+                };
+                board.set_cell(next(81), cell);
+            }
+
+            for index in 0..81 {
+                for turn in [Turn::Black, Turn::White] {
+                    for win_len in [3, 4, 5] {
+                        assert_eq!(
+                            board.completes_line(index, turn, win_len),
+                            board
+                                .count_consecutive_cells(index, turn)
+                                .contains(&win_len),
+                            "index {index}, {turn:?}, win length {win_len}:\n{board}",
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical_hash() {
+        let mut board = Board::new(15);
+        board.set_cell(16, Cell::Black);
+        board.set_cell(17, Cell::Black);
+        board.set_cell(33, Cell::White);
+
+        // rotate by 90 degrees clockwise and swap the colors
+        let mut equivalent = Board::new(15);
+        for (index, &cell) in board.cells().iter().enumerate() {
+            let x = index % 15;
+            let y = index / 15;
+            equivalent.set_cell(x * 15 + (14 - y), cell.swapped());
+        }
+
+        assert_ne!(board.cells(), equivalent.cells());
+        assert_eq!(board.canonical_hash(), equivalent.canonical_hash());
+
+        let mut different = board.clone();
+        different.set_cell(100, Cell::White);
+        assert_ne!(board.canonical_hash(), different.canonical_hash());
+    }
+
+    #[test]
+    fn test_canonical_legal_moves() {
+        let board = Board::new(15);
+        let moves = board.canonical_legal_moves();
+
+        // one move per orbit of the 8 symmetries: the triangle of an octant, center included
+        assert_eq!(moves.len(), 36);
+
+        for index in board.legal_moves() {
+            let representatives = Symmetry::ALL
+                .into_iter()
+                .map(|symmetry| symmetry.map_index(index, 15))
+                .filter(|index| moves.contains(index))
+                .collect::<HashSet<_>>();
+            assert_eq!(representatives.len(), 1);
+        }
+
+        // a stone off every axis of symmetry leaves no duplicates
+        let mut board = Board::new(15);
+        board.set_cell(17, Cell::Black);
+        assert_eq!(board.canonical_legal_moves(), board.legal_moves());
+
+        // a stone on the main diagonal keeps the mirror over it
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        let moves = board.canonical_legal_moves();
+        assert_eq!(moves.len(), (224 + 14) / 2);
+        assert!(moves.contains(&1));
+        assert!(!moves.contains(&15));
+    }
+
+    #[test]
+    fn test_canonical_symmetry() {
+        let mut board = Board::new(15);
+        board.set_cell(17, Cell::Black);
+        board.set_cell(50, Cell::White);
+        board.set_cell(200, Cell::Black);
+
+        let canonical = board.apply_symmetry(board.canonical_symmetry());
+
+        for symmetry in Symmetry::ALL {
+            let transformed = board.apply_symmetry(symmetry);
+            assert!(canonical.cells() <= transformed.cells());
+            assert_eq!(
+                transformed.apply_symmetry(transformed.canonical_symmetry()),
+                canonical
+            );
+        }
+
+        assert_eq!(Board::new(15).canonical_symmetry(), Symmetry::Identity);
+    }
+
+    #[test]
+    fn test_apply_symmetry() {
+        let mut board = Board::new(15);
+        board.set_cell(1, Cell::Black);
+        board.set_cell(17, Cell::White);
+        board.set_cell(50, Cell::Black);
+        board.set_cell(200, Cell::White);
+
+        for symmetry in Symmetry::ALL {
+            let transformed = board.apply_symmetry(symmetry);
+
+            for (index, &cell) in board.cells().iter().enumerate() {
+                assert_eq!(
+                    transformed.get_cell(symmetry.map_index(index, 15)),
+                    Some(cell),
+                    "{:?} at {}",
+                    symmetry,
+                    index
+                );
+            }
+        }
+
+        assert_eq!(Symmetry::Rot90.map_index(0, 15), 14);
+        assert_eq!(Symmetry::Rot180.map_index(0, 15), 224);
+        assert_eq!(Symmetry::Rot270.map_index(0, 15), 210);
+        assert_eq!(Symmetry::DiagAnti.map_index(0, 15), 224);
+        assert_eq!(Symmetry::DiagMain.map_index(1, 15), 15);
+    }
+
+    #[test]
+    fn test_symmetry_inverse() {
+        for symmetry in Symmetry::ALL {
+            for index in 0..15 * 15 {
+                let mapped = symmetry.map_index(index, 15);
+                assert_eq!(symmetry.inverse().map_index(mapped, 15), index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_min_stones_to_win() {
+        let mut board = Board::new(15);
+        assert_eq!(board.min_stones_to_win(Turn::Black, 5), Some(5));
+
+        // open four on the 8th row
+        for index in 106..110 {
+            board.set_cell(index, Cell::Black);
+        }
+        assert_eq!(board.min_stones_to_win(Turn::Black, 5), Some(1));
+        assert_eq!(board.min_stones_to_win(Turn::White, 5), Some(5));
+
+        // a three squeezed between white stones and the edge can't become a five
+        let mut board = Board::new(15);
+        board.set_cell(0, Cell::Black);
+        board.set_cell(1, Cell::Black);
+        board.set_cell(2, Cell::Black);
+        board.set_cell(3, Cell::White);
+        board.set_cell(15, Cell::White);
+        board.set_cell(16, Cell::White);
+        assert_eq!(board.min_stones_to_win(Turn::Black, 5), Some(4));
+
+        // no window at all when the board is smaller than the win length
+        assert_eq!(Board::new(4).min_stones_to_win(Turn::Black, 5), None);
+    }
 }
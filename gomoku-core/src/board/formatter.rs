@@ -0,0 +1,142 @@
+use super::{Board, Cell};
+use crate::game::Turn;
+
+/// Renders boards as text with configurable symbols, coordinates and cell separator.
+///
+/// The default formatter renders the same text as `Board`'s `Display` implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardFormatter {
+    empty: char,
+    black: char,
+    white: char,
+    coordinates: bool,
+    separator: String,
+}
+
+impl Default for BoardFormatter {
+    fn default() -> Self {
+        Self {
+            empty: Cell::Empty.symbol(),
+            black: Cell::Black.symbol(),
+            white: Cell::White.symbol(),
+            coordinates: true,
+            separator: " ".to_owned(),
+        }
+    }
+}
+
+impl BoardFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the symbols of empty cells, black stones and white stones.
+    pub fn symbols(mut self, empty: char, black: char, white: char) -> Self {
+        self.empty = empty;
+        self.black = black;
+        self.white = white;
+        self
+    }
+
+    /// Sets whether the column letters and row numbers are shown.
+    pub fn coordinates(mut self, coordinates: bool) -> Self {
+        self.coordinates = coordinates;
+        self
+    }
+
+    /// Sets the text written after each cell.
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    pub fn format(&self, board: &Board) -> String {
+        let board_size = board.board_size();
+        let mut result = String::new();
+
+        if self.coordinates {
+            result.push_str("   ");
+            for x in 0..board_size {
+                result.push((b'A' + x as u8) as char);
+                result.push_str(&self.separator);
+            }
+            result.push('\n');
+        }
+
+        for y in 0..board_size {
+            if self.coordinates {
+                result.push_str(&format!("{:2} ", y + 1));
+            }
+
+            for x in 0..board_size {
+                result.push(match board.cells()[y * board_size + x] {
+                    Cell::Empty => self.empty,
+                    Cell::Black => self.black,
+                    Cell::White => self.white,
+                });
+                result.push_str(&self.separator);
+            }
+
+            if y < board_size - 1 {
+                result.push('\n');
+            }
+        }
+
+        result
+    }
+}
+
+/// Describes a move for logs as the player's symbol, the position and the index, e.g.
+/// `X H8 (idx 112)`. An index off the board is shown with `?` as its position.
+pub fn format_move(board: &Board, index: usize, turn: Turn) -> String {
+    let position = board
+        .index_to_position(index)
+        .unwrap_or_else(|| "?".to_owned());
+
+    format!("{} {} (idx {})", Cell::from(turn).symbol(), position, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_board() -> Board {
+        let mut board = Board::new(3);
+        board.set_cell(4, Cell::Black);
+        board.set_cell(8, Cell::White);
+        board
+    }
+
+    #[test]
+    fn test_format_move() {
+        let board = Board::new(15);
+
+        assert_eq!(format_move(&board, 112, Turn::Black), "X H8 (idx 112)");
+        assert_eq!(format_move(&board, 0, Turn::White), "O A1 (idx 0)");
+        assert_eq!(format_move(&board, 225, Turn::Black), "X ? (idx 225)");
+    }
+
+    #[test]
+    fn test_default_matches_display() {
+        let board = sample_board();
+
+        assert_eq!(BoardFormatter::new().format(&board), board.to_string());
+    }
+
+    #[test]
+    fn test_unicode_symbols() {
+        let formatter = BoardFormatter::new().symbols('┼', '●', '○');
+
+        assert_eq!(
+            formatter.format(&sample_board()),
+            "   A B C \n 1 ┼ ┼ ┼ \n 2 ┼ ● ┼ \n 3 ┼ ┼ ○ "
+        );
+    }
+
+    #[test]
+    fn test_without_coordinates() {
+        let formatter = BoardFormatter::new().coordinates(false).separator("");
+
+        assert_eq!(formatter.format(&sample_board()), "...\n.X.\n..O");
+    }
+}
@@ -13,6 +13,13 @@ pub struct Index {
 }
 
 impl Index {
+    pub fn from_index(index: usize, board_size: usize) -> Self {
+        Self {
+            row: index / board_size,
+            column: index % board_size,
+        }
+    }
+
     pub fn is_valid(self, board_size: usize) -> bool {
         self.row < board_size && self.column < board_size
     }
@@ -135,9 +142,7 @@ impl<'a> IndexParser<'a> {
         }
 
         let number_index = number_to_index(number.as_str()) - 1; // 1-indexed to 0-indexed
-        let row = number_index / self.board_size;
-        let column = number_index % self.board_size;
-        let index = Index { row, column };
+        let index = Index::from_index(number_index, self.board_size);
 
         if index.is_valid(self.board_size) {
             return Some(index);
@@ -0,0 +1,60 @@
+use super::{Board, Cell};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PackedBoardError {
+    #[error("invalid packed length {actual}, expected {expected}")]
+    InvalidLength { expected: usize, actual: usize },
+    #[error("invalid cell value {value} at index {index}")]
+    InvalidCell { index: usize, value: u8 },
+}
+
+impl Board {
+    /// Packs the cells into 2 bits each, 4 cells per byte, starting from the low bits.
+    ///
+    /// Empty, black and white cells are encoded as `0`, `1` and `2`. The unused bits of the last
+    /// byte are zero.
+    pub fn to_packed(&self) -> Vec<u8> {
+        let mut packed = vec![0u8; packed_len(self.board_size)];
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let value = match cell {
+                Cell::Empty => 0,
+                Cell::Black => 1,
+                Cell::White => 2,
+            };
+            packed[index / 4] |= value << (index % 4 * 2);
+        }
+
+        packed
+    }
+
+    /// Unpacks a board produced by `to_packed`.
+    pub fn from_packed(board_size: usize, packed: &[u8]) -> Result<Board, PackedBoardError> {
+        let expected = packed_len(board_size);
+
+        if packed.len() != expected {
+            return Err(PackedBoardError::InvalidLength {
+                expected,
+                actual: packed.len(),
+            });
+        }
+
+        let cells = (0..board_size * board_size)
+            .map(
+                |index| match (packed[index / 4] >> (index % 4 * 2)) & 0b11 {
+                    0 => Ok(Cell::Empty),
+                    1 => Ok(Cell::Black),
+                    2 => Ok(Cell::White),
+                    value => Err(PackedBoardError::InvalidCell { index, value }),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Board::from_cells(board_size, cells))
+    }
+}
+
+fn packed_len(board_size: usize) -> usize {
+    (board_size * board_size).div_ceil(4)
+}
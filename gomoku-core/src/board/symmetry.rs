@@ -0,0 +1,59 @@
+/// One of the 8 rotations/reflections of a square board.
+///
+/// Rotations are clockwise, with the first row at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symmetry {
+    Identity,
+    Rot90,
+    Rot180,
+    Rot270,
+    /// Mirrors the columns (left <-> right).
+    FlipH,
+    /// Mirrors the rows (top <-> bottom).
+    FlipV,
+    /// Reflects over the main diagonal (top-left to bottom-right).
+    DiagMain,
+    /// Reflects over the anti-diagonal (top-right to bottom-left).
+    DiagAnti,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rot90,
+        Symmetry::Rot180,
+        Symmetry::Rot270,
+        Symmetry::FlipH,
+        Symmetry::FlipV,
+        Symmetry::DiagMain,
+        Symmetry::DiagAnti,
+    ];
+
+    /// Returns the symmetry undoing this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Symmetry::Rot90 => Symmetry::Rot270,
+            Symmetry::Rot270 => Symmetry::Rot90,
+            symmetry => symmetry,
+        }
+    }
+
+    /// Maps a board index to the index it moves to under this symmetry.
+    pub fn map_index(self, index: usize, board_size: usize) -> usize {
+        let last = board_size - 1;
+        let x = index % board_size;
+        let y = index / board_size;
+        let (x, y) = match self {
+            Symmetry::Identity => (x, y),
+            Symmetry::Rot90 => (last - y, x),
+            Symmetry::Rot180 => (last - x, last - y),
+            Symmetry::Rot270 => (y, last - x),
+            Symmetry::FlipH => (last - x, y),
+            Symmetry::FlipV => (x, last - y),
+            Symmetry::DiagMain => (y, x),
+            Symmetry::DiagAnti => (last - y, last - x),
+        };
+
+        y * board_size + x
+    }
+}
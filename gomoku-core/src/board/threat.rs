@@ -0,0 +1,410 @@
+use super::{Board, Cell};
+use crate::game::Turn;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThreatKind {
+    /// A line one stone short of winning, open on both ends (e.g. `.XXXX.`). It can't be blocked.
+    OpenFour,
+    /// A line one stone short of winning that can be completed in exactly one way (e.g. `OXXXX.`,
+    /// `XX.XX`).
+    Four,
+    /// A line two stones short of winning that can become an open four (e.g. `.XXX..`, `.XX.X.`).
+    OpenThree,
+}
+
+impl ThreatKind {
+    /// A rough value of the threat, used by `Board::threat_balance`.
+    pub fn weight(self) -> f64 {
+        match self {
+            ThreatKind::OpenFour => 8.0,
+            ThreatKind::Four => 4.0,
+            ThreatKind::OpenThree => 2.0,
+        }
+    }
+}
+
+/// A threat of a player along a single row, column or diagonal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Threat {
+    pub kind: ThreatKind,
+    /// The player's stones forming the threat, ordered along the line.
+    pub stones: Vec<usize>,
+    /// The empty cells that complete the threat (for fours) or extend it (for open threes), sorted.
+    pub gaps: Vec<usize>,
+}
+
+/// A whole row, column or diagonal of the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LineId {
+    Row(usize),
+    Column(usize),
+    /// A top-left to bottom-right diagonal, by its column minus its row.
+    Diagonal(isize),
+    /// A top-right to bottom-left diagonal, by its row plus its column.
+    AntiDiagonal(usize),
+}
+
+impl Board {
+    /// Lists the open fours, fours and open threes of the given player.
+    ///
+    /// Threats are found from windows of `win_len` cells (fours) and `win_len + 1` cells with empty
+    /// ends (open fours and open threes). Overlapping windows with the same stones are merged into a
+    /// single threat, and a four is not reported again when its stones already form an open four.
+    pub fn threats(&self, turn: Turn, win_len: usize) -> Vec<Threat> {
+        let own = Cell::from(turn);
+        let opponent = Cell::from(turn.next());
+        let mut threats = vec![];
+
+        if win_len < 3 {
+            return threats;
+        }
+
+        // open fours and open threes: `win_len + 1` cells with both ends empty
+        for window in self.line_windows(win_len + 1) {
+            let (first, last) = (window[0], window[window.len() - 1]);
+
+            if !self.cells[first].is_empty() || !self.cells[last].is_empty() {
+                continue;
+            }
+
+            let interior = &window[1..window.len() - 1];
+
+            if interior.iter().any(|&index| self.cells[index] == opponent) {
+                continue;
+            }
+
+            let (stones, empties): (Vec<usize>, Vec<usize>) = interior
+                .iter()
+                .partition(|&&index| self.cells[index] == own);
+
+            if stones.len() == win_len - 1 {
+                add_threat(
+                    &mut threats,
+                    ThreatKind::OpenFour,
+                    stones,
+                    vec![first, last],
+                );
+            } else if stones.len() == win_len - 2 {
+                let mut gaps = empties;
+                gaps.extend([first, last]);
+                add_threat(&mut threats, ThreatKind::OpenThree, stones, gaps);
+            }
+        }
+
+        // fours: `win_len` cells with a single empty one
+        for window in self.line_windows(win_len) {
+            if window.iter().any(|&index| self.cells[index] == opponent) {
+                continue;
+            }
+
+            let (stones, empties): (Vec<usize>, Vec<usize>) =
+                window.iter().partition(|&&index| self.cells[index] == own);
+
+            if stones.len() != win_len - 1 {
+                continue;
+            }
+
+            let is_open_four = threats
+                .iter()
+                .any(|threat| threat.kind == ThreatKind::OpenFour && threat.stones == stones);
+
+            if !is_open_four {
+                add_threat(&mut threats, ThreatKind::Four, stones, empties);
+            }
+        }
+
+        for threat in threats.iter_mut() {
+            threat.gaps.sort_unstable();
+            threat.gaps.dedup();
+        }
+
+        threats.sort_by(|lhs, rhs| (lhs.kind, &lhs.stones).cmp(&(rhs.kind, &rhs.stones)));
+        threats
+    }
+
+    /// Lists the cells where the opponent of `turn` would win on their next move, i.e. the cells
+    /// `turn` has to play to block. More than one cell means the opponent can't be stopped.
+    pub fn blocking_moves(&self, turn: Turn, win_len: usize) -> Vec<usize> {
+        let mut moves = self
+            .threats(turn.next(), win_len)
+            .into_iter()
+            .filter(|threat| threat.kind != ThreatKind::OpenThree)
+            .flat_map(|threat| threat.gaps)
+            .collect::<Vec<_>>();
+        moves.sort_unstable();
+        moves.dedup();
+        moves
+    }
+
+    /// Counts the lines through `index` on which placing a stone of `turn` there would form a four,
+    /// open four or open three. Two or more is a double threat. Returns zero for an occupied cell.
+    pub fn threats_created(&self, index: usize, turn: Turn, win_len: usize) -> usize {
+        if !self.get_cell(index).is_some_and(Cell::is_empty) {
+            return 0;
+        }
+
+        let mut board = self.clone();
+        board.set_cell(index, turn.into());
+
+        let (row, column) = board.to_coords(index).unwrap();
+        let mut directions = board
+            .threats(turn, win_len)
+            .into_iter()
+            .filter(|threat| threat.stones.contains(&index))
+            .filter_map(|threat| {
+                // the direction of the line, from any other stone of the threat
+                let other = threat.stones.into_iter().find(|&stone| stone != index)?;
+                let (other_row, other_column) = board.to_coords(other).unwrap();
+                let direction = (
+                    (other_row as isize - row as isize).signum(),
+                    (other_column as isize - column as isize).signum(),
+                );
+                Some(if direction < (0, 0) {
+                    (-direction.0, -direction.1)
+                } else {
+                    direction
+                })
+            })
+            .collect::<Vec<_>>();
+        directions.sort_unstable();
+        directions.dedup();
+        directions.len()
+    }
+
+    /// Maps every empty cell taking part in a threat of `turn`, as one of its gaps, to the lines of
+    /// those threats, sorted. A cell on several lines adds to independent threats, while cells
+    /// sharing their only line are redundant with each other.
+    pub fn threat_cells(&self, turn: Turn, win_len: usize) -> HashMap<usize, Vec<LineId>> {
+        let mut cells = HashMap::<usize, Vec<LineId>>::new();
+
+        for threat in self.threats(turn, win_len) {
+            // every threat spans at least two cells, as its gaps include both ends of open ones
+            let mut line_cells = threat.stones.iter().chain(&threat.gaps);
+            let (Some(&first), Some(&second)) = (line_cells.next(), line_cells.next()) else {
+                continue;
+            };
+            let line = self.line_through(first, second);
+
+            for gap in threat.gaps {
+                cells.entry(gap).or_default().push(line);
+            }
+        }
+
+        for lines in cells.values_mut() {
+            lines.sort_unstable();
+            lines.dedup();
+        }
+
+        cells
+    }
+
+    /// Returns the line through two distinct cells lying on a common row, column or diagonal.
+    fn line_through(&self, first: usize, second: usize) -> LineId {
+        let (row, column) = self.to_coords(first).unwrap();
+        let (other_row, other_column) = self.to_coords(second).unwrap();
+
+        if row == other_row {
+            LineId::Row(row)
+        } else if column == other_column {
+            LineId::Column(column)
+        } else if (row < other_row) == (column < other_column) {
+            LineId::Diagonal(column as isize - row as isize)
+        } else {
+            LineId::AntiDiagonal(row + column)
+        }
+    }
+
+    /// Sums the weights of `turn`'s threats minus those of the opponent's, as a cheap evaluation of
+    /// the position from `turn`'s perspective.
+    pub fn threat_balance(&self, turn: Turn, win_len: usize) -> f64 {
+        let score = |turn| {
+            self.threats(turn, win_len)
+                .iter()
+                .map(|threat| threat.kind.weight())
+                .sum::<f64>()
+        };
+
+        score(turn) - score(turn.next())
+    }
+
+    /// Like `blocking_moves`, but only keeps the cells where the opponent's stone would actually
+    /// complete a line of exactly `win_len`, so completions that would make an overline are dropped.
+    pub fn forced_defenses(&self, turn: Turn, win_len: usize) -> Vec<usize> {
+        let opponent = turn.next();
+
+        self.blocking_moves(turn, win_len)
+            .into_iter()
+            .filter(|&index| {
+                let mut board = self.clone();
+                board.set_cell(index, opponent.into());
+                board
+                    .count_consecutive_cells(index, opponent)
+                    .contains(&win_len)
+            })
+            .collect()
+    }
+
+    /// Lists the cells where a stone of `turn` would complete a line of exactly `win_len` right
+    /// away, sorted.
+    pub fn winning_moves(&self, turn: Turn, win_len: usize) -> Vec<usize> {
+        self.forced_defenses(turn.next(), win_len)
+    }
+
+    /// Whether the opponent of `turn` could win with their next move, so that `turn` must respond.
+    pub fn opponent_has_immediate_win(&self, turn: Turn, win_len: usize) -> bool {
+        !self.winning_moves(turn.next(), win_len).is_empty()
+    }
+
+    /// Lists the legal moves that stop every immediate win of the opponent of `turn` at once.
+    ///
+    /// Without any opponent four, every legal move does. An empty result means the opponent has
+    /// at least two winning cells, e.g. an open four or a double four, and the position is lost.
+    pub fn single_move_defenses(&self, turn: Turn, win_len: usize) -> Vec<usize> {
+        let winning_cells = self.forced_defenses(turn, win_len);
+
+        match winning_cells.len() {
+            0 => self.legal_moves(),
+            1 => winning_cells,
+            _ => vec![],
+        }
+    }
+}
+
+/// Adds a threat, merging its gaps into an existing one of the same kind and stones.
+fn add_threat(threats: &mut Vec<Threat>, kind: ThreatKind, stones: Vec<usize>, gaps: Vec<usize>) {
+    match threats
+        .iter_mut()
+        .find(|threat| threat.kind == kind && threat.stones == stones)
+    {
+        Some(threat) => threat.gaps.extend(gaps),
+        None => threats.push(Threat { kind, stones, gaps }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(black: &[usize], white: &[usize]) -> Board {
+        let mut board = Board::new(15);
+
+        for &index in black {
+            board.set_cell(index, Cell::Black);
+        }
+
+        for &index in white {
+            board.set_cell(index, Cell::White);
+        }
+
+        board
+    }
+
+    #[test]
+    fn test_threats_created() {
+        // row 7 and column 7 each hold three black stones leading up to (7, 7)
+        let board = board_with(&[109, 110, 111, 67, 82, 97], &[]);
+
+        assert_eq!(board.threats_created(112, Turn::Black, 5), 2);
+        assert_eq!(board.threats_created(108, Turn::Black, 5), 1);
+        assert_eq!(board.threats_created(0, Turn::Black, 5), 0);
+        assert_eq!(board.threats_created(109, Turn::Black, 5), 0);
+    }
+
+    #[test]
+    fn test_threat_cells() {
+        // an open three on row 7 and another on column 9, both extended by (7, 9)
+        let board = board_with(&[111, 112, 113, 129, 144, 159], &[]);
+        let cells = board.threat_cells(Turn::Black, 5);
+
+        assert_eq!(cells[&114], vec![LineId::Row(7), LineId::Column(9)]);
+        assert_eq!(cells[&110], vec![LineId::Row(7)]);
+        assert_eq!(cells[&174], vec![LineId::Column(9)]);
+        assert!(!cells.contains_key(&112));
+        assert!(board.threat_cells(Turn::White, 5).is_empty());
+
+        // a four on a diagonal and one on an anti-diagonal, crossing at (5, 5)
+        let board = board_with(&[16, 32, 48, 64, 136, 122, 108, 94], &[0, 150]);
+        let cells = board.threat_cells(Turn::Black, 5);
+
+        assert_eq!(
+            cells[&80],
+            vec![LineId::Diagonal(0), LineId::AntiDiagonal(10)]
+        );
+    }
+
+    #[test]
+    fn test_opponent_has_immediate_win() {
+        // row 7: `.OOOO.`
+        let board = board_with(&[0, 30, 60], &[106, 107, 108, 109]);
+
+        assert!(board.opponent_has_immediate_win(Turn::Black, 5));
+        assert!(!board.opponent_has_immediate_win(Turn::White, 5));
+        assert_eq!(board.winning_moves(Turn::White, 5), vec![105, 110]);
+
+        // scattered stones
+        let board = board_with(&[112, 0], &[113, 224]);
+
+        assert!(!board.opponent_has_immediate_win(Turn::Black, 5));
+        assert!(!board.opponent_has_immediate_win(Turn::White, 5));
+        assert!(board.winning_moves(Turn::Black, 5).is_empty());
+    }
+
+    #[test]
+    fn test_threat_balance() {
+        // row 7: `.XXXX.`
+        let board = board_with(&[106, 107, 108, 109], &[0, 30]);
+
+        let balance = board.threat_balance(Turn::Black, 5);
+        assert!(0.0 < balance);
+        assert_eq!(board.threat_balance(Turn::White, 5), -balance);
+        assert_eq!(Board::new(15).threat_balance(Turn::Black, 5), 0.0);
+    }
+
+    #[test]
+    fn test_forced_defenses_single_four() {
+        // row 0: `OXXXX.`
+        let board = board_with(&[1, 2, 3, 4], &[0]);
+
+        assert_eq!(board.forced_defenses(Turn::White, 5), vec![5]);
+    }
+
+    #[test]
+    fn test_forced_defenses_double_four() {
+        // row 0: `XXXX.`, column 14: four stones from row 1 down
+        let board = board_with(&[0, 1, 2, 3, 29, 44, 59, 74], &[]);
+
+        assert_eq!(board.forced_defenses(Turn::White, 5), vec![4, 14, 89]);
+    }
+
+    #[test]
+    fn test_single_move_defenses() {
+        // row 0: `OXXXX.`
+        let board = board_with(&[1, 2, 3, 4], &[0]);
+        assert_eq!(board.single_move_defenses(Turn::White, 5), vec![5]);
+
+        // row 0: `XXXX.`, column 14: four stones from row 1 down
+        let board = board_with(&[0, 1, 2, 3, 29, 44, 59, 74], &[]);
+        assert!(board.single_move_defenses(Turn::White, 5).is_empty());
+
+        // two fours sharing their completion: row 0 `XX.XX`, and column 2 below it, blocked at the
+        // bottom
+        let board = board_with(&[0, 1, 3, 4, 17, 32, 47, 62], &[77]);
+        assert_eq!(board.single_move_defenses(Turn::White, 5), vec![2]);
+
+        let board = board_with(&[112], &[]);
+        assert_eq!(
+            board.single_move_defenses(Turn::White, 5),
+            board.legal_moves()
+        );
+    }
+
+    #[test]
+    fn test_forced_defenses_skips_overline() {
+        // row 0: `XXX.XX` — filling the gap makes six in a row
+        let board = board_with(&[0, 1, 2, 4, 5], &[]);
+
+        assert!(!board.blocking_moves(Turn::White, 5).is_empty());
+        assert!(board.forced_defenses(Turn::White, 5).is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::board::{Board, Cell};
+use crate::board::{Board, Cell, Threat, ThreatKind};
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -53,23 +53,142 @@ pub struct Game {
     turn: Turn,
     turn_count: usize,
     history: Vec<(Turn, Board)>,
+    moves: Vec<usize>,
     game_result: Option<GameResult>,
     board: Board,
+    /// The game is drawn once this many stones are on the board.
+    move_limit: Option<usize>,
 }
 
 impl Game {
+    /// Creates a game on an empty board.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_consecutive_stones` is out of `2..=board_size`; see `try_new`.
     pub fn new(board_size: usize, max_consecutive_stones: usize) -> Self {
-        Self {
+        match Self::try_new(board_size, max_consecutive_stones) {
+            Ok(game) => game,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates a game on an empty board, checking that a line of `max_consecutive_stones` is
+    /// neither trivial nor impossible, i.e. that it is in `2..=board_size`.
+    pub fn try_new(
+        board_size: usize,
+        max_consecutive_stones: usize,
+    ) -> Result<Self, GameConfigError> {
+        check_win_length(board_size, max_consecutive_stones)?;
+
+        Ok(Self {
             board_size,
             max_consecutive_stones,
             turn: Turn::Black,
             turn_count: 0,
             history: vec![(Turn::Black, Board::new(board_size))],
+            moves: vec![],
             game_result: None,
             board: Board::new(board_size),
+            move_limit: None,
+        })
+    }
+
+    /// Replays the given moves from an empty board.
+    pub fn from_moves(
+        board_size: usize,
+        max_consecutive_stones: usize,
+        moves: &[usize],
+    ) -> Result<Self, FromMovesError> {
+        let mut game = Self::try_new(board_size, max_consecutive_stones)?;
+
+        for (move_index, &index) in moves.iter().enumerate() {
+            game.place_stone(index)
+                .map_err(|source| FromMovesError::InvalidMove { move_index, source })?;
+        }
+
+        game.recompute_result();
+        Ok(game)
+    }
+
+    /// Starts a game from an arbitrary position, with `turn` to move.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_consecutive_stones` is out of `2..=board_size`; see `try_from_board`.
+    pub fn from_board(board: Board, turn: Turn, max_consecutive_stones: usize) -> Self {
+        match Self::try_from_board(board, turn, max_consecutive_stones) {
+            Ok(game) => game,
+            Err(err) => panic!("{}", err),
         }
     }
 
+    /// Starts a game from an arbitrary position, with `turn` to move, checking the win length like
+    /// `try_new`.
+    ///
+    /// The moves that led to the position are unknown, so the history only holds the given board and
+    /// `moves` is empty. The result is recomputed from the board.
+    pub fn try_from_board(
+        board: Board,
+        turn: Turn,
+        max_consecutive_stones: usize,
+    ) -> Result<Self, GameConfigError> {
+        check_win_length(board.board_size(), max_consecutive_stones)?;
+
+        let mut game = Self {
+            board_size: board.board_size(),
+            max_consecutive_stones,
+            turn,
+            turn_count: board.illegal_moves().len(),
+            history: vec![(turn, board.clone())],
+            moves: vec![],
+            game_result: None,
+            board,
+            move_limit: None,
+        };
+        game.recompute_result();
+        Ok(game)
+    }
+
+    /// Starts a game with handicap stones already on the board, placed in the given order.
+    ///
+    /// White moves next if black has more stones than white, and black otherwise. Like with
+    /// `from_board`, the history only holds the handicap board and `moves` is empty.
+    pub fn with_handicap(
+        board_size: usize,
+        max_consecutive_stones: usize,
+        placements: &[(Turn, usize)],
+    ) -> Result<Self, HandicapError> {
+        Self::try_new(board_size, max_consecutive_stones)?;
+        let mut board = Board::new(board_size);
+
+        for (placement, &(turn, index)) in placements.iter().enumerate() {
+            let source = match board.get_cell(index) {
+                Some(Cell::Empty) => {
+                    board.set_cell(index, turn.into());
+                    continue;
+                }
+                Some(stone) => PlaceStoneError::StoneAlreadyPlaced { index, stone },
+                None => PlaceStoneError::InvalidIndex {
+                    index,
+                    max_allowed_index: board_size * board_size,
+                },
+            };
+
+            return Err(HandicapError::InvalidPlacement { placement, source });
+        }
+
+        let black = board.stones(Cell::Black).len();
+        let white = board.stones(Cell::White).len();
+        let turn = if white < black {
+            Turn::White
+        } else {
+            Turn::Black
+        };
+
+        Ok(Self::from_board(board, turn, max_consecutive_stones))
+    }
+
     pub fn board_size(&self) -> usize {
         self.board_size
     }
@@ -90,6 +209,48 @@ impl Game {
         &self.history
     }
 
+    /// Returns the board after `n` moves, so `board_at(0)` is the starting board and
+    /// `board_at(moves().len())` the current one. Returns `None` past the current board.
+    ///
+    /// For a game started with `from_board`, the starting board is the given one and `n` counts
+    /// the moves played since.
+    pub fn board_at(&self, n: usize) -> Option<&Board> {
+        self.history.get(n).map(|(_, board)| board)
+    }
+
+    /// Returns the result the game had after `n` moves, indexed like `board_at`. Returns `None`
+    /// while the game was undecided and past the current board.
+    pub fn result_at(&self, n: usize) -> Option<GameResult> {
+        if n == self.moves.len() {
+            return self.game_result;
+        }
+
+        let (turn, board) = self.history.get(n)?;
+        let mut game = Self::from_board(board.clone(), *turn, self.max_consecutive_stones);
+        game.set_move_limit(self.move_limit);
+        game.game_result
+    }
+
+    /// Returns the indices of the stones placed so far, in order.
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    /// Returns the index of the most recent move, or `None` if no move was played.
+    pub fn last_move(&self) -> Option<usize> {
+        self.moves.last().copied()
+    }
+
+    /// Returns the Chebyshev distance of the most recent move from `Board::center_index`, i.e. the
+    /// number of king steps between them, or `None` if no move was played.
+    pub fn last_move_center_distance(&self) -> Option<f64> {
+        let (row, column) = self.board.to_coords(self.last_move()?)?;
+        let (center_row, center_column) = self.board.to_coords(self.board.center_index())?;
+        let distance = row.abs_diff(center_row).max(column.abs_diff(center_column));
+
+        Some(distance as f64)
+    }
+
     pub fn game_result(&self) -> Option<GameResult> {
         self.game_result
     }
@@ -97,6 +258,111 @@ impl Game {
     pub fn board(&self) -> &Board {
         &self.board
     }
+
+    pub fn move_limit(&self) -> Option<usize> {
+        self.move_limit
+    }
+
+    /// Sets the number of stones on the board at which the game is drawn, or `None` to play until
+    /// the board is full. The result is recomputed for the new limit.
+    pub fn set_move_limit(&mut self, move_limit: Option<usize>) {
+        self.move_limit = move_limit;
+        self.recompute_result();
+    }
+
+    /// Returns how many moves can still be played before the game is drawn: the empty cells, or
+    /// fewer if the move limit is reached first. Runs in constant time, as the board keeps count of
+    /// its empty cells.
+    pub fn moves_remaining(&self) -> usize {
+        let empty = self.board.empty_count();
+
+        match self.move_limit {
+            Some(move_limit) => empty.min(move_limit.saturating_sub(self.turn_count)),
+            None => empty,
+        }
+    }
+
+    /// Lists the open fours, fours and open threes of both players.
+    pub fn threat_report(&self) -> ThreatReport {
+        ThreatReport {
+            black: self.board.threats(Turn::Black, self.max_consecutive_stones),
+            white: self.board.threats(Turn::White, self.max_consecutive_stones),
+        }
+    }
+
+    /// Roughly classifies how far the game has progressed.
+    ///
+    /// The game is in its endgame once it is over, half the board is filled, or either player has
+    /// a four. It is still in its opening during the first `board_size / 2` moves, as long as
+    /// nobody has an open three yet. Anything else is the midgame.
+    pub fn phase(&self) -> GamePhaseLabel {
+        let report = self.threat_report();
+        let threats = || report.black.iter().chain(&report.white);
+
+        if self.game_result.is_some()
+            || self.board_size * self.board_size <= 2 * self.turn_count
+            || threats().any(|threat| threat.kind != ThreatKind::OpenThree)
+        {
+            GamePhaseLabel::Endgame
+        } else if self.turn_count < self.board_size / 2 && threats().next().is_none() {
+            GamePhaseLabel::Opening
+        } else {
+            GamePhaseLabel::Midgame
+        }
+    }
+
+    /// Recomputes the game result by scanning the board, rather than from the last move.
+    ///
+    /// If both players have a winning line, the player who moved last is taken as the winner.
+    pub fn recompute_result(&mut self) {
+        let last_player = self.turn.next();
+
+        self.game_result = if self.board.is_won(last_player, self.max_consecutive_stones) {
+            Some(GameResult::Win(last_player))
+        } else if self.board.is_won(self.turn, self.max_consecutive_stones) {
+            Some(GameResult::Win(self.turn))
+        } else if self.moves_remaining() == 0 {
+            Some(GameResult::Draw)
+        } else {
+            None
+        };
+    }
+}
+
+/// Checks that a line of `win_len` stones is neither trivial nor impossible on the board.
+fn check_win_length(board_size: usize, win_len: usize) -> Result<(), GameConfigError> {
+    if !(2..=board_size).contains(&win_len) {
+        return Err(GameConfigError::InvalidWinLength {
+            board_size,
+            win_len,
+        });
+    }
+
+    Ok(())
+}
+
+/// The stage of a game; see `Game::phase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GamePhaseLabel {
+    Opening,
+    Midgame,
+    Endgame,
+}
+
+/// The threats of both players on the board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreatReport {
+    pub black: Vec<Threat>,
+    pub white: Vec<Threat>,
+}
+
+impl ThreatReport {
+    pub fn get(&self, turn: Turn) -> &[Threat] {
+        match turn {
+            Turn::Black => &self.black,
+            Turn::White => &self.white,
+        }
+    }
 }
 
 pub struct PlaceStoneResult {
@@ -104,11 +370,69 @@ pub struct PlaceStoneResult {
     pub stone: Cell,
     pub turn_was: Turn,
     pub board_was: Board,
-    /// The number of consecutive stones placed by the current player.
-    pub consecutive_stones: Vec<usize>,
     pub game_result: Option<GameResult>,
 }
 
+impl PlaceStoneResult {
+    /// The lengths of the lines through the placed stone, as `Board::count_consecutive_cells`
+    /// reports them. Computed on request from `board_was`, as the win check doesn't need them.
+    pub fn consecutive_stones(&self) -> Vec<usize> {
+        self.board_was
+            .count_consecutive_cells_placing(self.index, self.turn_was)
+    }
+
+    /// Lists the state changes caused by the move, in order, for frontends to react to.
+    pub fn events(&self) -> Vec<GameEvent> {
+        let mut events = vec![GameEvent::MovePlaced {
+            index: self.index,
+            turn: self.turn_was,
+        }];
+
+        if let Some(game_result) = self.game_result {
+            events.push(GameEvent::GameEnded(game_result));
+        }
+
+        events
+    }
+}
+
+/// A change of a game's state; see `PlaceStoneResult::events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameEvent {
+    MovePlaced { index: usize, turn: Turn },
+    GameEnded(GameResult),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GameConfigError {
+    #[error("win length {win_len} is out of range for board size {board_size}")]
+    InvalidWinLength { board_size: usize, win_len: usize },
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum HandicapError {
+    #[error(transparent)]
+    InvalidConfig(#[from] GameConfigError),
+    /// The placement at the given zero-based position of the list can't be made.
+    #[error("invalid handicap placement {placement}: {source}")]
+    InvalidPlacement {
+        placement: usize,
+        source: PlaceStoneError,
+    },
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum FromMovesError {
+    #[error(transparent)]
+    InvalidConfig(#[from] GameConfigError),
+    /// The move at the given zero-based position of the list can't be played.
+    #[error("invalid move {move_index}: {source}")]
+    InvalidMove {
+        move_index: usize,
+        source: PlaceStoneError,
+    },
+}
+
 #[derive(Error, Debug, Clone)]
 pub enum PlaceStoneError {
     #[error("invalid index {index}")]
@@ -121,14 +445,19 @@ pub enum PlaceStoneError {
 }
 
 impl Game {
+    /// Places a stone of the current player and updates the result.
+    ///
+    /// A move wins when it completes a line of exactly `max_consecutive_stones` in any direction,
+    /// even if it also makes a longer line in another. The win takes precedence over the draw when
+    /// the move fills the last empty cell or reaches the move limit; a move that only makes an
+    /// overline there is a draw.
     pub fn place_stone(&mut self, index: usize) -> Result<PlaceStoneResult, PlaceStoneError> {
-        let max_allowed_index = self.board.board_size() * self.board.board_size();
         let cell = match self.board.get_cell(index) {
             Some(cell) => cell,
             None => {
                 return Err(PlaceStoneError::InvalidIndex {
                     index,
-                    max_allowed_index,
+                    max_allowed_index: self.board.board_size() * self.board.board_size(),
                 });
             }
         };
@@ -140,9 +469,9 @@ impl Game {
         let board_was = self.board.clone();
         self.board.set_cell(index, self.turn.into());
 
-        let consecutive_stones = self.board.count_consecutive_cells(index, self.turn);
         let is_winning_move =
-            consecutive_stones.first().copied() == Some(self.max_consecutive_stones);
+            self.board
+                .completes_line(index, self.turn, self.max_consecutive_stones);
 
         let turn_was = self.turn;
         self.turn = self.turn.next();
@@ -150,21 +479,53 @@ impl Game {
 
         if is_winning_move {
             self.game_result = Some(GameResult::Win(turn_was));
-        } else if self.turn_count == max_allowed_index {
+        } else if self.moves_remaining() == 0 {
             self.game_result = Some(GameResult::Draw);
         }
 
         self.history.push((self.turn, self.board.clone()));
+        self.moves.push(index);
 
         Ok(PlaceStoneResult {
             index,
             stone: self.turn.into(),
             turn_was,
             board_was,
-            consecutive_stones,
             game_result: self.game_result,
         })
     }
+
+    /// Takes back the last move, restoring the position, turn and result from before it, and
+    /// returns what placing that stone had returned. Returns `None` if no move was played; a game
+    /// started with `from_board` can't be undone past its starting board.
+    pub fn undo(&mut self) -> Option<PlaceStoneResult> {
+        let index = self.moves.pop()?;
+        let game_result = self.game_result;
+
+        self.history.pop();
+        let (turn, board) = self.history.last().unwrap();
+        let board_was = board.clone();
+
+        self.turn = *turn;
+        self.turn_count -= 1;
+        self.board = board_was.clone();
+        self.recompute_result();
+
+        Some(PlaceStoneResult {
+            index,
+            stone: self.turn.next().into(),
+            turn_was: self.turn,
+            board_was,
+            game_result,
+        })
+    }
+
+    /// Returns a copy of the game with the stone placed, leaving this game untouched.
+    pub fn with_move(&self, index: usize) -> Result<Game, PlaceStoneError> {
+        let mut game = self.clone();
+        game.place_stone(index)?;
+        Ok(game)
+    }
 }
 
 impl Display for Game {
@@ -182,3 +543,422 @@ impl Display for Game {
         write!(f, "{}", self.board)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recompute_result() {
+        let mut game = Game::from_moves(15, 5, &[0, 15, 1, 16, 2, 17, 3, 18, 4]).unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+
+        // a stale result is restored from the board alone
+        game.game_result = None;
+        game.recompute_result();
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+
+        let mut game = Game::from_moves(15, 5, &[0, 15, 1, 16, 2, 17, 3]).unwrap();
+        assert_eq!(game.game_result(), None);
+
+        game.game_result = Some(GameResult::Win(Turn::White));
+        game.recompute_result();
+        assert_eq!(game.game_result(), None);
+    }
+
+    /// Builds a game from rows of `B`, `W` and `.`, with black to move.
+    fn game_from_rows(rows: &[&str], max_consecutive_stones: usize) -> Game {
+        let mut board = Board::new(rows.len());
+
+        for (index, symbol) in rows.concat().chars().enumerate() {
+            match symbol {
+                'B' => board.set_cell(index, Cell::Black),
+                'W' => board.set_cell(index, Cell::White),
+                _ => {}
+            }
+        }
+
+        Game::from_board(board, Turn::Black, max_consecutive_stones)
+    }
+
+    #[test]
+    fn test_win_on_last_empty_cell() {
+        let mut game = game_from_rows(&["WWB", "W.B", "BBW"], 3);
+        assert_eq!(game.game_result(), None);
+
+        let result = game.place_stone(4).unwrap();
+        assert_eq!(result.game_result, Some(GameResult::Win(Turn::Black)));
+        assert_eq!(game.turn_count(), 9);
+    }
+
+    #[test]
+    fn test_overline_on_last_empty_cell() {
+        // the last cell makes five in the top row, and exactly four in the second column
+        let mut game = game_from_rows(&["B.BBB", "WBWWW", "WBWWB", "BBBWB", "WWWBW"], 4);
+        assert_eq!(game.game_result(), None);
+        assert_eq!(
+            game.place_stone(1).unwrap().game_result,
+            Some(GameResult::Win(Turn::Black))
+        );
+
+        // the last cell only makes five in the top row
+        let mut game = game_from_rows(&["B.BBB", "WWBWW", "BBWBW", "WWWBW", "BWWBB"], 4);
+        assert_eq!(game.game_result(), None);
+        assert_eq!(
+            game.place_stone(1).unwrap().game_result,
+            Some(GameResult::Draw)
+        );
+    }
+
+    #[test]
+    fn test_full_board_draw() {
+        let board = crate::board::fill_draw_board(15);
+        let game = Game::from_board(board, Turn::White, 5);
+
+        assert_eq!(game.game_result(), Some(GameResult::Draw));
+        assert_eq!(game.moves_remaining(), 0);
+        assert_eq!(game.phase(), GamePhaseLabel::Endgame);
+    }
+
+    #[test]
+    fn test_undo() {
+        let moves = [112, 0, 113, 1, 114, 2, 115, 3, 116];
+        let mut game = Game::new(15, 5);
+        assert!(game.undo().is_none());
+
+        for &index in &moves {
+            game.place_stone(index).unwrap();
+        }
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+
+        // undoing the winning move reopens the game
+        let result = game.undo().unwrap();
+        assert_eq!(result.index, 116);
+        assert_eq!(result.turn_was, Turn::Black);
+        assert_eq!(result.game_result, Some(GameResult::Win(Turn::Black)));
+        assert_eq!(&result.board_was, game.board());
+        assert_eq!(game.game_result(), None);
+        assert_eq!(game.turn(), Turn::Black);
+        assert_eq!(game.turn_count(), 8);
+        assert_eq!(game.moves(), &moves[..8]);
+        assert_eq!(game.history().len(), 9);
+
+        let mut undone = vec![116];
+        while let Some(result) = game.undo() {
+            undone.push(result.index);
+        }
+        undone.reverse();
+
+        assert_eq!(undone, moves);
+        assert_eq!(game.board(), &Board::new(15));
+        assert_eq!(game.board().legal_moves().len(), 225);
+        assert_eq!(game.turn(), Turn::Black);
+        assert_eq!(game.turn_count(), 0);
+        assert_eq!(game.history().len(), 1);
+
+        // the game plays on as if fresh
+        for &index in &moves {
+            game.place_stone(index).unwrap();
+        }
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+    }
+
+    #[test]
+    fn test_undo_draw() {
+        let mut game = game_from_rows(&["BWB", "W.B", "WBW"], 3);
+        game.place_stone(4).unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::Draw));
+
+        let result = game.undo().unwrap();
+        assert_eq!(result.game_result, Some(GameResult::Draw));
+        assert_eq!(game.game_result(), None);
+        assert_eq!(game.moves_remaining(), 1);
+
+        // the starting board of `from_board` stays
+        assert!(game.undo().is_none());
+        assert_eq!(game.board().legal_moves(), vec![4]);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut game = Game::new(15, 5);
+        let mut events = vec![];
+
+        for index in [0, 15, 1, 16, 2, 17, 3, 18, 4] {
+            events.extend(game.place_stone(index).unwrap().events());
+        }
+
+        assert_eq!(events.len(), 10);
+        assert_eq!(
+            events[..2],
+            [
+                GameEvent::MovePlaced {
+                    index: 0,
+                    turn: Turn::Black,
+                },
+                GameEvent::MovePlaced {
+                    index: 15,
+                    turn: Turn::White,
+                },
+            ]
+        );
+        assert_eq!(
+            events[8..],
+            [
+                GameEvent::MovePlaced {
+                    index: 4,
+                    turn: Turn::Black,
+                },
+                GameEvent::GameEnded(GameResult::Win(Turn::Black)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_moves_remaining() {
+        let mut game = Game::from_moves(15, 5, &[112, 113, 97, 98]).unwrap();
+        assert_eq!(game.moves_remaining(), 221);
+
+        let full = game_from_rows(&["WWB", "W.B", "BBW"], 3);
+        assert_eq!(full.moves_remaining(), 1);
+
+        game.set_move_limit(Some(6));
+        assert_eq!(game.moves_remaining(), 2);
+        assert_eq!(game.game_result(), None);
+
+        game.place_stone(0).unwrap();
+        let result = game.place_stone(1).unwrap();
+        assert_eq!(result.game_result, Some(GameResult::Draw));
+        assert_eq!(game.moves_remaining(), 0);
+
+        game.set_move_limit(None);
+        assert_eq!(game.game_result(), None);
+        assert_eq!(game.moves_remaining(), 219);
+    }
+
+    #[test]
+    fn test_try_new() {
+        for win_len in [0, 1, 16] {
+            assert_eq!(
+                Game::try_new(15, win_len).unwrap_err(),
+                GameConfigError::InvalidWinLength {
+                    board_size: 15,
+                    win_len,
+                }
+            );
+        }
+
+        assert!(Game::try_new(15, 5).is_ok());
+        assert!(Game::try_new(15, 15).is_ok());
+        assert!(Game::try_new(3, 2).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "win length 1 is out of range")]
+    fn test_new_rejects_trivial_win_length() {
+        Game::new(15, 1);
+    }
+
+    #[test]
+    fn test_board_at() {
+        let mut game = Game::new(15, 5);
+        let moves = [112, 0, 113, 1, 114, 2, 115, 3, 116];
+
+        for &index in &moves {
+            game.place_stone(index).unwrap();
+        }
+
+        assert_eq!(game.board_at(0), Some(&Board::new(15)));
+        assert_eq!(game.board_at(moves.len()), Some(game.board()));
+        assert_eq!(game.board_at(moves.len() + 1), None);
+
+        for n in 0..=moves.len() {
+            let board = game.board_at(n).unwrap();
+            assert_eq!(board.illegal_moves().len(), n);
+
+            for (i, &index) in moves.iter().enumerate() {
+                let expected = match (i < n, i % 2) {
+                    (false, _) => Cell::Empty,
+                    (true, 0) => Cell::Black,
+                    (true, _) => Cell::White,
+                };
+                assert_eq!(board.get_cell(index), Some(expected));
+            }
+
+            let expected = (n == moves.len()).then_some(GameResult::Win(Turn::Black));
+            assert_eq!(game.result_at(n), expected);
+        }
+
+        assert_eq!(game.result_at(moves.len() + 1), None);
+    }
+
+    #[test]
+    fn test_with_handicap() {
+        let game = Game::with_handicap(15, 5, &[(Turn::Black, 112), (Turn::Black, 96)]).unwrap();
+        assert_eq!(game.turn(), Turn::White);
+        assert_eq!(game.turn_count(), 2);
+        assert_eq!(game.board().get_cell(96), Some(Cell::Black));
+        assert_eq!(game.history().len(), 1);
+        assert_eq!(game.game_result(), None);
+
+        let game = Game::with_handicap(15, 5, &[(Turn::Black, 112), (Turn::White, 96)]).unwrap();
+        assert_eq!(game.turn(), Turn::Black);
+
+        let game = Game::with_handicap(15, 5, &[]).unwrap();
+        assert_eq!(game.turn(), Turn::Black);
+        assert_eq!(game.board(), &Board::new(15));
+
+        // a handicap line is already a win
+        let placements = Vec::from_iter((0..5).map(|index| (Turn::White, index)));
+        let game = Game::with_handicap(15, 5, &placements).unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::White)));
+
+        assert!(matches!(
+            Game::with_handicap(15, 5, &[(Turn::Black, 112), (Turn::White, 112)]),
+            Err(HandicapError::InvalidPlacement {
+                placement: 1,
+                source: PlaceStoneError::StoneAlreadyPlaced {
+                    index: 112,
+                    stone: Cell::Black
+                },
+            })
+        ));
+        assert!(matches!(
+            Game::with_handicap(15, 5, &[(Turn::Black, 225)]),
+            Err(HandicapError::InvalidPlacement {
+                placement: 0,
+                source: PlaceStoneError::InvalidIndex { index: 225, .. },
+            })
+        ));
+        assert!(matches!(
+            Game::with_handicap(15, 16, &[]),
+            Err(HandicapError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_last_move_center_distance() {
+        let mut game = Game::new(15, 5);
+        assert_eq!(game.last_move(), None);
+        assert_eq!(game.last_move_center_distance(), None);
+
+        game.place_stone(112).unwrap();
+        assert_eq!(game.last_move(), Some(112));
+        assert_eq!(game.last_move_center_distance(), Some(0.0));
+
+        game.place_stone(224).unwrap();
+        assert_eq!(game.last_move(), Some(224));
+        assert_eq!(game.last_move_center_distance(), Some(7.0));
+
+        // a knight's move away is two king steps
+        game.place_stone(129).unwrap();
+        assert_eq!(game.last_move_center_distance(), Some(2.0));
+    }
+
+    #[test]
+    fn test_from_board() {
+        let played = Game::from_moves(15, 5, &[112, 113, 97]).unwrap();
+        let mut game = Game::from_board(played.board().clone(), Turn::White, 5);
+
+        assert_eq!(game.board(), played.board());
+        assert_eq!(game.turn(), Turn::White);
+        assert_eq!(game.turn_count(), 3);
+        assert_eq!(game.game_result(), None);
+        assert!(game.moves().is_empty());
+
+        game.place_stone(0).unwrap();
+        assert_eq!(game.board().get_cell(0), Some(Cell::White));
+
+        let won = Game::from_moves(15, 5, &[0, 15, 1, 16, 2, 17, 3, 18, 4]).unwrap();
+        let game = Game::from_board(won.board().clone(), Turn::White, 5);
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+
+        for win_len in [1, 16] {
+            assert_eq!(
+                Game::try_from_board(Board::new(15), Turn::Black, win_len).unwrap_err(),
+                GameConfigError::InvalidWinLength {
+                    board_size: 15,
+                    win_len,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_moves_errors() {
+        assert!(matches!(
+            Game::from_moves(15, 16, &[112]),
+            Err(FromMovesError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Game::from_moves(15, 5, &[112, 113, 112]),
+            Err(FromMovesError::InvalidMove {
+                move_index: 2,
+                source: PlaceStoneError::StoneAlreadyPlaced { index: 112, .. },
+            })
+        ));
+    }
+
+    #[test]
+    fn test_with_move() {
+        let game = Game::from_moves(15, 5, &[112]).unwrap();
+        let next = game.with_move(113).unwrap();
+
+        assert_eq!(game.moves(), &[112]);
+        assert_eq!(game.turn(), Turn::White);
+        assert_eq!(game.board().get_cell(113), Some(Cell::Empty));
+
+        assert_eq!(next.moves(), &[112, 113]);
+        assert_eq!(next.turn(), Turn::Black);
+        assert_eq!(next.board().get_cell(113), Some(Cell::White));
+
+        assert!(matches!(
+            game.with_move(112),
+            Err(PlaceStoneError::StoneAlreadyPlaced { index: 112, .. })
+        ));
+        assert_eq!(game.moves(), &[112]);
+    }
+
+    #[test]
+    fn test_phase() {
+        assert_eq!(Game::new(15, 5).phase(), GamePhaseLabel::Opening);
+
+        let game = Game::from_moves(15, 5, &[112, 113, 97]).unwrap();
+        assert_eq!(game.phase(), GamePhaseLabel::Opening);
+
+        // black: .XXX.. on row 7
+        let game = Game::from_moves(15, 5, &[109, 0, 110, 30, 111]).unwrap();
+        assert_eq!(game.phase(), GamePhaseLabel::Midgame);
+
+        // black: .XXXX. on row 7
+        let game = Game::from_moves(15, 5, &[108, 154, 109, 155, 110, 140, 111]).unwrap();
+        assert_eq!(game.phase(), GamePhaseLabel::Endgame);
+
+        let game = game_from_rows(&["WWB", "W.B", "BBW"], 3);
+        assert_eq!(game.phase(), GamePhaseLabel::Endgame);
+    }
+
+    #[test]
+    fn test_threat_report() {
+        // black: .XXXX. on row 7 (columns 3-6), white: .OOO.. on row 10 (columns 4-6)
+        let game = Game::from_moves(15, 5, &[108, 154, 109, 155, 110, 156, 111]).unwrap();
+        let report = game.threat_report();
+
+        assert_eq!(
+            report.get(Turn::Black),
+            &[Threat {
+                kind: ThreatKind::OpenFour,
+                stones: vec![108, 109, 110, 111],
+                gaps: vec![107, 112],
+            }]
+        );
+        assert_eq!(
+            report.get(Turn::White),
+            &[Threat {
+                kind: ThreatKind::OpenThree,
+                stones: vec![154, 155, 156],
+                gaps: vec![152, 153, 157, 158],
+            }]
+        );
+    }
+}
@@ -1,2 +1,4 @@
+pub mod analysis;
 pub mod board;
 pub mod game;
+pub mod sgf;
@@ -0,0 +1,395 @@
+use crate::{
+    board::{Board, Cell},
+    game::{Game, GameConfigError, GameResult, PlaceStoneError, Turn},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum SgfError {
+    #[error("invalid SGF: {reason}")]
+    InvalidFormat { reason: String },
+    #[error("invalid board size {value}")]
+    InvalidBoardSize { value: String },
+    #[error(transparent)]
+    InvalidConfig(#[from] GameConfigError),
+    #[error("invalid coordinate {value} at move {move_number}")]
+    InvalidCoordinate { move_number: usize, value: String },
+    /// An `AB`/`AW` setup stone is off the board or on an occupied cell.
+    #[error("invalid setup stone {value}")]
+    InvalidSetupStone { value: String },
+    #[error("invalid player to move {value}")]
+    InvalidPlayer { value: String },
+    #[error("expected {} to play at move {move_number}", expected.name())]
+    UnexpectedColor { move_number: usize, expected: Turn },
+    #[error("illegal move at move {move_number}: {source}")]
+    IllegalMove {
+        move_number: usize,
+        source: PlaceStoneError,
+    },
+}
+
+impl Game {
+    /// Imports a game from an SGF record, such as one produced by `to_sgf`.
+    ///
+    /// Only the board size (`SZ`, 15 if absent), the setup stones (`AB`/`AW`), the player to move
+    /// first (`PL`, black if absent) and the moves (`B`/`W`) are read; the win length is not part
+    /// of the record and must be given. A record with setup stones or `PL` starts like
+    /// `Game::from_board`, from the setup position. Moves are numbered from 1 in errors.
+    pub fn from_sgf(sgf: &str, max_consecutive_stones: usize) -> Result<Game, SgfError> {
+        let properties = parse_properties(sgf)?;
+        let board_size = match properties.iter().find(|(id, _)| id == "SZ") {
+            Some((_, value)) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|&size| 0 < size && size <= 52)
+                .ok_or_else(|| SgfError::InvalidBoardSize {
+                    value: value.clone(),
+                })?,
+            None => 15,
+        };
+
+        let mut game = Game::try_new(board_size, max_consecutive_stones)?;
+        let mut board = Board::new(board_size);
+        let mut setup = false;
+
+        for (id, value) in &properties {
+            let cell = match id.as_str() {
+                "AB" => Cell::Black,
+                "AW" => Cell::White,
+                _ => continue,
+            };
+
+            match parse_coord(value, board_size) {
+                Some(index) if board.get_cell(index) == Some(Cell::Empty) => {
+                    board.set_cell(index, cell);
+                    setup = true;
+                }
+                _ => {
+                    return Err(SgfError::InvalidSetupStone {
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        let first_turn = match properties.iter().find(|(id, _)| id == "PL") {
+            Some((_, value)) => Some(match value.as_str() {
+                "B" => Turn::Black,
+                "W" => Turn::White,
+                _ => {
+                    return Err(SgfError::InvalidPlayer {
+                        value: value.clone(),
+                    });
+                }
+            }),
+            None => None,
+        };
+
+        if setup || first_turn.is_some() {
+            game = Game::try_from_board(
+                board,
+                first_turn.unwrap_or(Turn::Black),
+                max_consecutive_stones,
+            )?;
+        }
+
+        let moves = properties
+            .iter()
+            .filter(|(id, _)| id == "B" || id == "W")
+            .enumerate();
+
+        for (i, (id, value)) in moves {
+            let move_number = i + 1;
+            let turn = if id == "B" { Turn::Black } else { Turn::White };
+
+            if turn != game.turn() {
+                return Err(SgfError::UnexpectedColor {
+                    move_number,
+                    expected: game.turn(),
+                });
+            }
+
+            let index =
+                parse_coord(value, board_size).ok_or_else(|| SgfError::InvalidCoordinate {
+                    move_number,
+                    value: value.clone(),
+                })?;
+
+            game.place_stone(index)
+                .map_err(|source| SgfError::IllegalMove {
+                    move_number,
+                    source,
+                })?;
+        }
+
+        game.recompute_result();
+        Ok(game)
+    }
+
+    /// Exports the game as an SGF record.
+    ///
+    /// The record uses `GM[4]` (gomoku) and stores each move as `{column}{row}`, where `a` is the
+    /// first column/row and `A` follows `z` on boards larger than 26. The result is recorded in `RE`
+    /// if the game is finished. A game started from a position, e.g. with `from_board` or
+    /// `with_handicap`, records its starting stones as `AB`/`AW` setup and the player to move first
+    /// in `PL`.
+    ///
+    /// Example:
+    /// - `(;GM[4]FF[4]SZ[15];B[hh];W[ih])`
+    /// - `(;GM[4]FF[4]SZ[15]AB[hh]PL[W];W[ih])`
+    pub fn to_sgf(&self) -> String {
+        let mut sgf = format!("(;GM[4]FF[4]SZ[{}]", self.board_size());
+
+        match self.game_result() {
+            Some(GameResult::Win(Turn::Black)) => sgf.push_str("RE[B+]"),
+            Some(GameResult::Win(Turn::White)) => sgf.push_str("RE[W+]"),
+            Some(GameResult::Draw) => sgf.push_str("RE[0]"),
+            None => {}
+        }
+
+        let (first_turn, start) = &self.history()[0];
+        let color = |turn: Turn| match turn {
+            Turn::Black => 'B',
+            Turn::White => 'W',
+        };
+        let coords = |index: usize| {
+            let (y, x) = start.to_coords(index).unwrap();
+            format!("[{}{}]", sgf_coord(x), sgf_coord(y))
+        };
+
+        let black = start.stones(Cell::Black);
+        let white = start.stones(Cell::White);
+        if !black.is_empty() {
+            sgf.push_str("AB");
+            sgf.extend(black.iter().map(|&index| coords(index)));
+        }
+        if !white.is_empty() {
+            sgf.push_str("AW");
+            sgf.extend(white.iter().map(|&index| coords(index)));
+        }
+        if !black.is_empty() || !white.is_empty() || *first_turn != Turn::Black {
+            sgf.push_str(&format!("PL[{}]", color(*first_turn)));
+        }
+
+        let mut turn = *first_turn;
+
+        for &index in self.moves() {
+            sgf.push_str(&format!(";{}{}", color(turn), coords(index)));
+            turn = turn.next();
+        }
+
+        sgf.push(')');
+        sgf
+    }
+}
+
+fn sgf_coord(value: usize) -> char {
+    if value < 26 {
+        (b'a' + value as u8) as char
+    } else {
+        (b'A' + (value - 26) as u8) as char
+    }
+}
+
+/// Parses a `{column}{row}` coordinate into a cell index.
+fn parse_coord(value: &str, board_size: usize) -> Option<usize> {
+    let mut coords = value.chars().map(sgf_coord_value);
+
+    match (coords.next(), coords.next(), coords.next()) {
+        (Some(Some(x)), Some(Some(y)), None) if x < board_size && y < board_size => {
+            Some(y * board_size + x)
+        }
+        _ => None,
+    }
+}
+
+fn sgf_coord_value(c: char) -> Option<usize> {
+    match c {
+        'a'..='z' => Some(c as usize - 'a' as usize),
+        'A'..='Z' => Some(c as usize - 'A' as usize + 26),
+        _ => None,
+    }
+}
+
+/// Parses the `(identifier, value)` pairs of all nodes, in order.
+///
+/// The game tree structure is ignored, so variations are not supported.
+fn parse_properties(sgf: &str) -> Result<Vec<(String, String)>, SgfError> {
+    let mut properties = vec![];
+    let mut id = String::new();
+    let mut chars = sgf.trim().chars();
+
+    if !sgf.trim().starts_with('(') {
+        return Err(SgfError::InvalidFormat {
+            reason: "missing game tree".to_owned(),
+        });
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' | ';' => id.clear(),
+            c if c.is_whitespace() => {}
+            c if c.is_ascii_uppercase() => {
+                // a new identifier starts after a value
+                if properties.last().is_some_and(|(last, _)| *last == id) {
+                    id.clear();
+                }
+
+                id.push(c);
+            }
+            '[' => {
+                if id.is_empty() {
+                    return Err(SgfError::InvalidFormat {
+                        reason: "value without property".to_owned(),
+                    });
+                }
+
+                let mut value = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(SgfError::InvalidFormat {
+                                reason: "unterminated value".to_owned(),
+                            });
+                        }
+                    }
+                }
+
+                properties.push((id.clone(), value));
+            }
+            c => {
+                return Err(SgfError::InvalidFormat {
+                    reason: format!("unexpected character {:?}", c),
+                });
+            }
+        }
+    }
+
+    Ok(properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_sgf() {
+        let mut game = Game::new(15, 5);
+        assert_eq!(game.to_sgf(), "(;GM[4]FF[4]SZ[15])");
+
+        game.place_stone(112).unwrap();
+        game.place_stone(113).unwrap();
+        game.place_stone(0).unwrap();
+        assert_eq!(game.to_sgf(), "(;GM[4]FF[4]SZ[15];B[hh];W[ih];B[aa])");
+
+        let mut game = Game::new(15, 5);
+        for index in [0, 15, 1, 16, 2, 17, 3, 18, 4] {
+            game.place_stone(index).unwrap();
+        }
+        assert!(game
+            .to_sgf()
+            .starts_with("(;GM[4]FF[4]SZ[15]RE[B+];B[aa];W[ab]"));
+    }
+
+    #[test]
+    fn test_from_sgf() {
+        let game = Game::from_sgf("(;GM[4]FF[4]SZ[15];B[hh];W[ih];B[aa])", 5).unwrap();
+        assert_eq!(game.moves(), &[112, 113, 0]);
+
+        let mut won = Game::new(15, 5);
+        for index in [0, 15, 1, 16, 2, 17, 3, 18, 4] {
+            won.place_stone(index).unwrap();
+        }
+
+        let game = Game::from_sgf(&won.to_sgf(), 5).unwrap();
+        assert_eq!(game.moves(), won.moves());
+        assert_eq!(game.game_result(), Some(GameResult::Win(Turn::Black)));
+
+        let game = Game::from_sgf("(;GM[4]\n;B[aa]\n;W[bb])", 5).unwrap();
+        assert_eq!(game.board_size(), 15);
+        assert_eq!(game.moves(), &[0, 16]);
+    }
+
+    #[test]
+    fn test_sgf_setup() {
+        let mut game = Game::with_handicap(15, 5, &[(Turn::Black, 112), (Turn::White, 0)]).unwrap();
+        game.place_stone(113).unwrap();
+        assert_eq!(game.to_sgf(), "(;GM[4]FF[4]SZ[15]AB[hh]AW[aa]PL[B];B[ih])");
+
+        let imported = Game::from_sgf(&game.to_sgf(), 5).unwrap();
+        assert_eq!(imported.board(), game.board());
+        assert_eq!(imported.moves(), game.moves());
+        assert_eq!(imported.turn(), game.turn());
+
+        let mut game = Game::with_handicap(15, 5, &[(Turn::Black, 112)]).unwrap();
+        game.place_stone(113).unwrap();
+        game.place_stone(0).unwrap();
+        assert_eq!(game.to_sgf(), "(;GM[4]FF[4]SZ[15]AB[hh]PL[W];W[ih];B[aa])");
+
+        let imported = Game::from_sgf(&game.to_sgf(), 5).unwrap();
+        assert_eq!(imported.board(), game.board());
+        assert_eq!(imported.history()[0], game.history()[0]);
+        assert_eq!(imported.moves(), game.moves());
+
+        let game = Game::from_board(Board::new(15), Turn::White, 5);
+        assert_eq!(game.to_sgf(), "(;GM[4]FF[4]SZ[15]PL[W])");
+        assert_eq!(
+            Game::from_sgf(&game.to_sgf(), 5).unwrap().turn(),
+            Turn::White
+        );
+    }
+
+    #[test]
+    fn test_from_sgf_errors() {
+        assert!(matches!(
+            Game::from_sgf("GM[4]", 5),
+            Err(SgfError::InvalidFormat { .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[3])", 5),
+            Err(SgfError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[x])", 5),
+            Err(SgfError::InvalidBoardSize { .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15];B[zz])", 5),
+            Err(SgfError::InvalidCoordinate { move_number: 1, .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15];B[aa];B[bb])", 5),
+            Err(SgfError::UnexpectedColor {
+                move_number: 2,
+                expected: Turn::White
+            })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15];B[aa];W[aa])", 5),
+            Err(SgfError::IllegalMove { move_number: 2, .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15]AB[aa][zz])", 5),
+            Err(SgfError::InvalidSetupStone { .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15]AB[aa]AW[aa])", 5),
+            Err(SgfError::InvalidSetupStone { .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15]PL[X])", 5),
+            Err(SgfError::InvalidPlayer { .. })
+        ));
+        assert!(matches!(
+            Game::from_sgf("(;SZ[15]AB[aa];W[bb])", 5),
+            Err(SgfError::UnexpectedColor {
+                move_number: 1,
+                expected: Turn::Black
+            })
+        ));
+    }
+}
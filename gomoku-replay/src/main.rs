@@ -0,0 +1,101 @@
+use gomoku_core::game::Game;
+use std::io::Write;
+
+const MAX_CONSECUTIVE_STONES: usize = 5;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: gomoku-replay <sgf file>");
+            std::process::exit(1);
+        }
+    };
+
+    let sgf = match std::fs::read_to_string(&path) {
+        Ok(sgf) => sgf,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let record = match Game::from_sgf(&sgf, MAX_CONSECUTIVE_STONES) {
+        Ok(record) => record,
+        Err(err) => {
+            eprintln!("failed to load {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let move_count = record.moves().len();
+    let mut cursor = 0;
+
+    show(&record, cursor);
+
+    loop {
+        println!();
+        print!("next | prev | goto <n> | quit: ");
+        std::io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+
+        if std::io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+
+        let mut words = input.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (None, _) => continue,
+            (Some("quit" | "q"), _) => break,
+            (Some("next" | "n"), _) => {
+                if move_count <= cursor {
+                    println!("already at the last move");
+                    continue;
+                }
+
+                cursor += 1;
+            }
+            (Some("prev" | "p"), _) => {
+                if cursor == 0 {
+                    println!("already at the first move");
+                    continue;
+                }
+
+                cursor -= 1;
+            }
+            (Some("goto" | "g"), Some(n)) => match n.parse::<usize>() {
+                Ok(n) if n <= move_count => {
+                    cursor = n;
+                }
+                _ => {
+                    println!("invalid move number: {} (0-{})", n, move_count);
+                    continue;
+                }
+            },
+            (Some(command), _) => {
+                println!("unknown command: {}", command);
+                continue;
+            }
+        }
+
+        show(&record, cursor);
+    }
+}
+
+/// Rebuilds the game after the first `cursor` moves of the record.
+fn position_at(record: &Game, cursor: usize) -> Game {
+    Game::from_moves(
+        record.board_size(),
+        record.max_consecutive_stones(),
+        &record.moves()[..cursor],
+    )
+    .unwrap()
+}
+
+fn show(record: &Game, cursor: usize) {
+    println!("===========================");
+    println!("move: {}/{}", cursor, record.moves().len());
+    println!("{}", position_at(record, cursor));
+}
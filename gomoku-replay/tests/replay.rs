@@ -0,0 +1,42 @@
+use gomoku_core::game::Game;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+#[test]
+fn test_step_to_end() {
+    let mut recorded = Game::new(15, 5);
+
+    for index in [0, 15, 1, 16, 2, 17, 3, 18, 4] {
+        recorded.place_stone(index).unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!("gomoku-replay-{}.sgf", std::process::id()));
+    std::fs::write(&path, recorded.to_sgf()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gomoku-replay"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"next\nnext\nprev\ngoto 8\nnext\nnext\nquit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let last_show = stdout.rsplit("===========================").next().unwrap();
+
+    assert!(last_show.contains("move: 9/9"));
+    assert!(last_show.contains(&recorded.to_string()));
+    assert!(last_show.contains("state: black wins"));
+    assert!(stdout.contains("already at the last move"));
+}
@@ -1,21 +1,148 @@
 use figment::{
-    providers::{Format, Toml},
+    providers::{Env, Format, Toml},
     Figment,
 };
 use gomoku_agent::{agent_provider::AgentProvider, agents::gomoku_ddqn::GomokuDDQNProvider};
 
-const AGENT_PATH: &str = "agents/test";
+const USAGE: &str =
+    "usage: gomoku-ai [--epochs <n>] [--agent-dir <dir>] [--config <toml file>] [--resume | --fresh]";
+
+/// Command-line options of the training binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Args {
+    epochs: usize,
+    /// Directory holding the agent's weights (`agent.safetensors`).
+    agent_dir: String,
+    /// Training options file. Defaults to `config.toml` in the agent directory.
+    config: String,
+    /// Whether to continue from the weights saved in the agent directory, if any. On by default,
+    /// as the weights are overwritten after training; `--fresh` starts from new weights instead.
+    resume: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut epochs = 1000000;
+    let mut agent_dir = "agents/test".to_owned();
+    let mut config = None;
+    let mut resume = true;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "--epochs" => {
+                let value = value("--epochs")?;
+                epochs = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of epochs: {}", value))?;
+            }
+            "--agent-dir" => agent_dir = value("--agent-dir")?,
+            "--config" => config = Some(value("--config")?),
+            "--resume" => resume = true,
+            "--fresh" => resume = false,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+
+    let config = config.unwrap_or_else(|| format!("{}/config.toml", agent_dir));
+
+    Ok(Args {
+        epochs,
+        agent_dir,
+        config,
+        resume,
+    })
+}
 
 fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
     let mut agent = GomokuDDQNProvider.create_agent();
+    let agent_path = format!("{}/agent.safetensors", args.agent_dir);
+
+    if args.resume && std::fs::exists(&agent_path).unwrap() {
+        agent.load(&agent_path).unwrap();
+    }
 
-    if std::fs::exists(format!("{AGENT_PATH}/agent.safetensors")).unwrap() {
-        agent
-            .load(&format!("{AGENT_PATH}/agent.safetensors"))
-            .unwrap();
+    let config = load_config(&args.config);
+    agent.train(args.epochs, config).unwrap();
+    agent.save(&agent_path).unwrap();
+}
+
+/// Loads the training options from the TOML file at `path`, overridden by `GOMOKU_`-prefixed
+/// environment variables (e.g. `GOMOKU_LEARNING_RATE`). Nested keys are separated by `__`, as in
+/// `GOMOKU_REWARD__DEFENSIVE`.
+fn load_config(path: &str) -> Figment {
+    Figment::new()
+        .merge(Toml::file(path))
+        .merge(Env::prefixed("GOMOKU_").split("__"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            args(&[]).unwrap(),
+            Args {
+                epochs: 1000000,
+                agent_dir: "agents/test".to_owned(),
+                config: "agents/test/config.toml".to_owned(),
+                resume: true,
+            }
+        );
+        assert_eq!(
+            args(&["--agent-dir", "agents/run1", "--epochs", "50", "--resume"]).unwrap(),
+            Args {
+                epochs: 50,
+                agent_dir: "agents/run1".to_owned(),
+                config: "agents/run1/config.toml".to_owned(),
+                resume: true,
+            }
+        );
+        assert_eq!(
+            args(&["--config", "train.toml"]).unwrap().config,
+            "train.toml"
+        );
+        assert!(!args(&["--fresh"]).unwrap().resume);
+        assert!(args(&["--fresh", "--resume"]).unwrap().resume);
+
+        assert!(args(&["--epochs"]).is_err());
+        assert!(args(&["--epochs", "many"]).is_err());
+        assert!(args(&["--verbose"]).is_err());
     }
 
-    let config = Figment::new().merge(Toml::file(format!("{AGENT_PATH}/config.toml")));
-    agent.train(1000000, config).unwrap();
-    agent.save(AGENT_PATH).unwrap();
+    #[test]
+    fn test_env_overrides_file() {
+        let path = std::env::temp_dir().join(format!("gomoku-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "learning_rate = 0.1\nbatch_size = 64\n").unwrap();
+        std::env::set_var("GOMOKU_LEARNING_RATE", "0.5");
+
+        let config = load_config(path.to_str().unwrap());
+        let learning_rate = config.extract_inner::<f64>("learning_rate");
+        let batch_size = config.extract_inner::<usize>("batch_size");
+
+        std::env::remove_var("GOMOKU_LEARNING_RATE");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(learning_rate.unwrap(), 0.5);
+        assert_eq!(batch_size.unwrap(), 64);
+    }
 }